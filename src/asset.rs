@@ -0,0 +1,63 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Api, Coin, StdResult, Uint128};
+
+/// A fungible asset the contract can price boxes in or hold as a reward. Tokenfactory
+/// denoms are bank denoms, so they are `Native` as well.
+#[cw_serde]
+pub enum AssetInfo {
+    Native {
+        denom: String,
+    },
+    Cw20 {
+        contract_addr: Addr,
+    },
+}
+
+impl AssetInfo {
+    /// Bank denom or cw20 contract address, used as the registry key.
+    pub fn key(&self) -> String {
+        match self {
+            AssetInfo::Native { denom } => denom.clone(),
+            AssetInfo::Cw20 { contract_addr } => contract_addr.to_string(),
+        }
+    }
+
+    pub fn is_native(&self) -> bool {
+        matches!(self, AssetInfo::Native { .. })
+    }
+
+    /// Checks the cw20 contract address is a valid address on this chain.
+    pub fn validate(&self, api: &dyn Api) -> StdResult<AssetInfo> {
+        match self {
+            AssetInfo::Native { denom } => Ok(AssetInfo::Native {
+                denom: denom.clone(),
+            }),
+            AssetInfo::Cw20 { contract_addr } => Ok(AssetInfo::Cw20 {
+                contract_addr: api.addr_validate(contract_addr.as_str())?,
+            }),
+        }
+    }
+}
+
+/// Registry entry of an asset the owner accepts for prices and rewards.
+#[cw_serde]
+pub struct AllowedAsset {
+    pub info: AssetInfo,
+    pub decimals: u64,
+}
+
+/// An amount of an `AssetInfo`, used for box prices, token rewards and payments.
+#[cw_serde]
+pub struct Asset {
+    pub info: AssetInfo,
+    pub amount: Uint128,
+}
+
+impl From<Coin> for Asset {
+    fn from(coin: Coin) -> Self {
+        Asset {
+            info: AssetInfo::Native { denom: coin.denom },
+            amount: coin.amount,
+        }
+    }
+}
@@ -1,27 +1,36 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
 
 use cosmwasm_std::{
     entry_point,
     from_json,
     to_json_binary,
-    CosmosMsg,
+    Addr,
+    Coin,
     DepsMut,
     Env,
     MessageInfo,
-    WasmMsg,
     Response,
+    Storage,
     Uint128,
     Deps,
     StdResult,
     Binary,
     Order,
+    Decimal,
+    Decimal256,
+    Uint256,
+    Event,
+    HexBinary,
 };
 use cw20::Cw20ReceiveMsg;
-use cw721::{ Cw721ExecuteMsg, Cw721ReceiveMsg };
-use cw_utils::must_pay;
+use cw721::Cw721ReceiveMsg;
+use cw_storage_plus::Bound;
+use cw_utils::{ must_pay, one_coin };
 
 use crate::{
+    asset::{ AllowedAsset, Asset, AssetInfo },
     error::ContractError,
+    merkle,
     msg::{
         ExecuteMsg,
         InstantiateMsg,
@@ -34,9 +43,81 @@ use crate::{
         TokenReceiveMsg,
         TokenFactoryReward,
         RewardType,
+        CreateBoxMsg,
+        OpenLimits,
+        BoxOddsResponse,
+        RewardOdds,
+        OddsModel,
+        BoxStatus,
+        ExpectedValueResponse,
+        DenomValue,
+        FeaturedBox,
+        FeaturedResponse,
+        AuctionsResponse,
+        AuctionBidsResponse,
+        TreasuryEntry,
+        TreasuryResponse,
+        AllowedAssetsResponse,
+        CreateBoxResponse,
+        AllowlistProof,
+        OpenAllowanceResponse,
+        OpenHistoryResponse,
+        KeyUsed,
+        CreditScope,
+        CreditRecipient,
+        CreditsResponse,
+        ReferralStatsResponse,
+        ReferralLeaderboardResponse,
+        JackpotsResponse,
+        JackpotHistoryResponse,
+        BoxKind,
+        RaffleTicketsResponse,
+        TradedNft,
+        TradeInDepositsResponse,
+        PendingRewardResponse,
+        BundleItem,
+        PendingBundlesResponse,
+        SeriesOdds,
+        SeriesResponse,
     },
-    state::{ Config, CONFIG, BOX_MAP, FortuneBox, ACCOUNT_MAP, UserInfo },
-    util,
+    state::{
+        Config,
+        CONFIG,
+        BOX_MAP,
+        FortuneBox,
+        ACCOUNT_MAP,
+        UserInfo,
+        FEATURED,
+        FeatureAuction,
+        FeatureBid,
+        AUCTIONS,
+        AUCTION_BIDS,
+        AUCTION_COUNT,
+        TREASURY,
+        ALLOWED_ASSETS,
+        OPEN_HISTORY,
+        OpenRecord,
+        CREDITS,
+        CREDIT_DROPS,
+        CREDIT_DROP_COUNT,
+        CREDIT_CLAIMS,
+        CreditDrop,
+        REFERRALS,
+        REFERRAL_BALANCES,
+        ReferralStats,
+        JACKPOTS,
+        JACKPOT_WINS,
+        BOX_OPENS,
+        WinnerStruct,
+        TRADE_IN_DEPOSITS,
+        PENDING_REWARDS,
+        PendingReward,
+        PENDING_BUNDLES,
+        PendingBundle,
+        SERIES,
+        Series,
+    },
+    util::{ self, DrawOutcome },
 };
 use cw2::set_contract_version;
 
@@ -53,14 +134,28 @@ pub fn instantiate(
     msg: InstantiateMsg
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    let owner = util::validate_addr(deps.api, "owner", &msg.owner)?;
+    util::validate_addr(deps.api, "founder", &msg.founder_addr)?;
+    let dev_addr = util::validate_addr(deps.api, "dev", &msg.dev_addr)?;
+    // The native token is always accepted, everything else has to be registered by the owner
+    let native = AssetInfo::Native { denom: msg.native_token.clone() };
+    ALLOWED_ASSETS.save(deps.storage, native.key(), &(AllowedAsset { info: native, decimals: 18 }))?;
+
     let config = Config {
-        owner: msg.owner.clone(),
-        creator: msg.owner.clone(),
-        native_token: msg.native_token.clone(),
-        injscribed_address: msg.dev_addr.clone(),
+        owner: owner.clone(),
+        creator: owner,
+        native_token: msg.native_token,
+        injscribed_address: dev_addr,
         feature_fees: Uint128::from(400000000000000000u128),
+        feature_period: 86400,
+        max_featured: 6,
         max_odds: 1000,
+        min_box_odds: 100,
+        max_box_odds: 1_000_000,
         enabled: true,
+        referral_bps: 2000,
+        jackpot_bps: 0,
+        jackpot_odds: 0,
     };
 
     CONFIG.save(deps.storage, &config)?;
@@ -76,76 +171,205 @@ pub fn execute(
     msg: ExecuteMsg
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::CreateBox {
-            box_id,
-            price,
-            token_denom,
-            token_decimals,
-            token_type,
-            duration,
-        } =>
-            execute_create_box(
+        ExecuteMsg::CreateBox(msg) => execute_create_box(deps, info, msg),
+        ExecuteMsg::ReceiveNft(msg) => execute_receive_nft(deps, env, info, msg),
+        ExecuteMsg::Receive(msg) => execute_receive_token(deps, env, info, msg),
+        ExecuteMsg::AddTokenFactoryReward { box_id, rewards } =>
+            execute_add_tokenfactory_rewards(deps, info, box_id, rewards),
+        ExecuteMsg::UpdateRewardOdds { box_id, reward_id, odds } =>
+            execute_update_reward_odds(deps, info, box_id, reward_id, odds),
+        ExecuteMsg::AddBundleReward { box_id, odds, items } =>
+            execute_add_bundle_reward(deps, info, box_id, odds, items),
+        ExecuteMsg::DepositBundle { box_id, bundle_id } => {
+            if info.funds.is_empty() {
+                return Err(ContractError::PaymentFailed {});
+            }
+            let items = info.funds
+                .into_iter()
+                .map(|coin| BundleItem::Token { asset: coin.into() })
+                .collect();
+            deposit_bundle(deps.storage, &info.sender, box_id, bundle_id, items)
+        }
+        ExecuteMsg::CancelBundle { box_id, bundle_id } =>
+            execute_cancel_bundle(deps, info, box_id, bundle_id),
+        ExecuteMsg::RemoveReward { box_id, reward_id } =>
+            execute_remove_reward(deps, info, box_id, reward_id),
+        ExecuteMsg::TopUpReward { box_id, reward_id, count } =>
+            execute_top_up_reward(deps, info, box_id, reward_id, count),
+        ExecuteMsg::FeatureBox { box_id, slots } =>
+            execute_feature_box(deps, env, info, box_id, slots),
+        ExecuteMsg::OpenFeatureAuction { slots, min_bid, duration, feature_duration } =>
+            execute_open_feature_auction(
                 deps,
+                env,
                 info,
-                box_id,
-                price,
-                token_denom,
-                token_decimals,
-                token_type,
-                duration
+                slots,
+                min_bid,
+                duration,
+                feature_duration
             ),
-        ExecuteMsg::ReceiveNft(msg) => execute_receive_nft(deps, info, msg),
-        ExecuteMsg::Receive(msg) => execute_receive_token(deps, info, msg),
-        ExecuteMsg::AddTokenFactoryReward { box_id, rewards } =>
-            execute_add_tokenfactory_rewards(deps, info, box_id, rewards),
-        ExecuteMsg::CancelBox { box_id } => execute_cancel_box(deps, info, box_id),
-        ExecuteMsg::OpenBox { box_id } => execute_open_box(deps, env, info, box_id),
+        ExecuteMsg::BidFeatureAuction { auction_id, box_id } =>
+            execute_bid_feature_auction(deps, env, info, auction_id, box_id),
+        ExecuteMsg::SettleFeatureAuction { auction_id } =>
+            execute_settle_feature_auction(deps, env, auction_id),
+        ExecuteMsg::UpdateFeatureConfig { feature_fees, feature_period, max_featured } =>
+            util::execute_update_feature_config(
+                deps.storage,
+                info.sender,
+                feature_fees,
+                feature_period,
+                max_featured
+            ),
+        ExecuteMsg::WithdrawTreasury { asset, amount, recipient } =>
+            util::execute_withdraw_treasury(deps, env, info, asset, amount, recipient),
+        ExecuteMsg::RegisterAsset { info: asset, decimals } =>
+            util::execute_register_asset(deps, info, asset, decimals),
+        ExecuteMsg::RemoveAsset { info: asset } =>
+            util::execute_remove_asset(deps.storage, info.sender, asset),
+        ExecuteMsg::UpdateOddsBounds { min_box_odds, max_box_odds } =>
+            util::execute_update_odds_bounds(deps.storage, info.sender, min_box_odds, max_box_odds),
+        ExecuteMsg::CancelBox { box_id } => execute_cancel_box(deps, env, info, box_id),
+        ExecuteMsg::CreateSeries { series_id, box_id } =>
+            execute_create_series(deps, info, series_id, box_id),
+        ExecuteMsg::JoinSeries { series_id, box_id, odds } =>
+            execute_join_series(deps, info, series_id, box_id, odds),
+        ExecuteMsg::CloseSeries { series_id } => execute_close_series(deps, env, info, series_id),
+        ExecuteMsg::PublishBox { box_id, start_time } =>
+            execute_publish_box(deps, env, info, box_id, start_time),
+        ExecuteMsg::PauseBox { box_id, resume_at } =>
+            execute_pause_box(deps, env, info, box_id, resume_at),
+        ExecuteMsg::ResumeBox { box_id } => execute_resume_box(deps, env, info, box_id),
+        ExecuteMsg::OpenBox { box_id, proof, referrer } =>
+            execute_open_box(deps, env, info, box_id, proof, referrer),
+        ExecuteMsg::UpdateReferralShare { referral_bps } =>
+            util::execute_update_referral_share(deps.storage, info.sender, referral_bps),
+        ExecuteMsg::ClaimReferralRewards {} => util::execute_claim_referral_rewards(deps, info),
+        ExecuteMsg::UpdateJackpotConfig { jackpot_bps, jackpot_odds } =>
+            util::execute_update_jackpot_config(deps.storage, info.sender, jackpot_bps, jackpot_odds),
+        ExecuteMsg::SetJackpotSeed { asset, seed } =>
+            util::execute_set_jackpot_seed(deps.storage, info.sender, asset, seed),
+        ExecuteMsg::Reroll { box_id } => {
+            let payment = match one_coin(&info) {
+                Ok(payment) => payment,
+                Err(_) => {
+                    return Err(ContractError::PaymentFailed {});
+                }
+            };
+            execute_reroll(deps, env, info.sender, box_id, payment.into())
+        }
+        ExecuteMsg::Accept { box_id, address } => execute_accept(deps, env, info, box_id, address),
+        ExecuteMsg::WithdrawTradeIn { box_id } => execute_withdraw_trade_in(deps, info, box_id),
+        ExecuteMsg::DrawRaffle { box_id } => execute_draw_raffle(deps, env, box_id),
+        ExecuteMsg::SetAllowlist { box_id, root } => execute_set_allowlist(deps, info, box_id, root),
+        ExecuteMsg::IssueCredits { scope, recipients, expires_at } =>
+            execute_issue_credits(deps, info, scope, recipients, expires_at),
+        ExecuteMsg::CreateCreditDrop { scope, root, expires_at } =>
+            execute_create_credit_drop(deps, info, scope, root, expires_at),
+        ExecuteMsg::ClaimCredits { drop_id, count, proof } =>
+            execute_claim_credits(deps, env, info, drop_id, count, proof),
     }
 }
 
 pub fn execute_create_box(
     deps: DepsMut,
     info: MessageInfo,
-    box_id: String,
-    price: Uint128,
-    token_denom: String,
-    token_decimals: u64,
-    token_type: String,
-    duration: u64
+    msg: CreateBoxMsg
 ) -> Result<Response, ContractError> {
     let cfg = CONFIG.load(deps.storage)?;
+
+    let max_odds = msg.max_odds.unwrap_or(cfg.max_odds);
+    if max_odds < cfg.min_box_odds || max_odds > cfg.max_box_odds {
+        return Err(ContractError::InvalidMaxOdds {
+            min: cfg.min_box_odds,
+            max: cfg.max_box_odds,
+        });
+    }
+    let price_asset = util::allowed_asset(deps.storage, &msg.price.info)?;
+    util::check_merkle_root(&msg.allowlist_root)?;
+    let holder_gate = util::validate_holder_gate(deps.api, msg.holder_gate.unwrap_or_default())?;
+    let key = match msg.key {
+        Some(key) => Some(util::validate_box_key(deps.api, key)?),
+        None => None,
+    };
+    let trade_in = match msg.trade_in {
+        Some(trade_in) => Some(util::validate_trade_in(deps.api, trade_in)?),
+        None => None,
+    };
+    if key.is_some() && trade_in.is_some() {
+        return Err(ContractError::InvalidTradeIn {});
+    }
+    let kind = msg.kind.unwrap_or_default();
+    let reroll = match msg.reroll {
+        Some(reroll) => Some(util::validate_reroll(reroll)?),
+        None => None,
+    };
+    util::validate_duration(msg.duration)?;
+    let paid_in_tokens = key.is_some() || trade_in.is_some();
+    if let BoxKind::Raffle { min_tickets, max_tickets } = kind {
+        if msg.duration == 0 || paid_in_tokens || reroll.is_some() {
+            return Err(ContractError::InvalidRaffle {});
+        }
+        util::validate_raffle(min_tickets, max_tickets)?;
+    }
+
+    let box_id = match msg.box_id {
+        Some(box_id) => {
+            util::validate_box_id(&box_id)?;
+            box_id
+        }
+        None => util::next_box_id(deps.storage)?,
+    };
     let lootbox = BOX_MAP.load(deps.storage, box_id.clone());
 
-    let is_featured =
-        info.funds[0].amount == cfg.feature_fees && info.funds[0].denom == "inj".to_string();
     match lootbox {
-        Ok(_) => {
-            return Err(ContractError::ConflictID {});
-        }
+        Ok(_) => { Err(ContractError::ConflictID {}) }
         Err(_) => {
+            let odds_model = msg.odds_model.unwrap_or_default();
+            let allow_empty = msg.allow_empty.unwrap_or(false);
             let fbox = FortuneBox {
                 id: box_id.clone(),
                 creator: info.sender.clone(),
                 rewards: vec![],
-                max_odds: cfg.max_odds.clone(),
-                price,
-                token_denom: token_denom.clone(),
-                token_decimals,
-                token_type,
-                duration,
-                is_over: false,
-                is_featured,
+                max_odds,
+                price: msg.price.clone(),
+                price_decimals: price_asset.decimals,
+                duration: msg.duration,
+                status: BoxStatus::Draft,
+                start_time: None,
+                end_time: None,
+                resume_at: None,
+                odds_model: odds_model.clone(),
+                allow_empty,
+                consolation_points: msg.consolation_points.unwrap_or_default(),
+                opened: 0,
+                featured_until: None,
+                allowlist_root: msg.allowlist_root,
+                holder_gate,
+                open_limits: msg.open_limits.unwrap_or_default(),
+                key,
+                held_keys: vec![],
+                held_key_amount: Uint128::zero(),
+                kind: kind.clone(),
+                raffle_pot: Uint128::zero(),
+                trade_in,
+                reroll,
+                series: None,
                 winners: None,
             };
 
             BOX_MAP.save(deps.storage, box_id.clone(), &fbox)?;
             Ok(
                 Response::default()
+                    .set_data(to_json_binary(&(CreateBoxResponse { box_id: box_id.clone() }))?)
                     .add_attribute("action", "create_box")
-                    .add_attribute("id", box_id.clone())
-                    .add_attribute("token_denom", token_denom.clone())
-                    .add_attribute("price", price.clone())
-                    .add_attribute("duration", duration.to_string().clone())
+                    .add_attribute("id", box_id)
+                    .add_attribute("price_asset", msg.price.info.key())
+                    .add_attribute("price", msg.price.amount)
+                    .add_attribute("duration", msg.duration.to_string())
+                    .add_attribute("max_odds", max_odds.to_string())
+                    .add_attribute("odds_model", format!("{:?}", odds_model))
+                    .add_attribute("allow_empty", allow_empty.to_string())
+                    .add_attribute("kind", format!("{:?}", kind))
             )
         }
     }
@@ -153,6 +377,7 @@ pub fn execute_create_box(
 
 pub fn execute_receive_nft(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     wrapper: Cw721ReceiveMsg
 ) -> Result<Response, ContractError> {
@@ -160,42 +385,32 @@ pub fn execute_receive_nft(
 
     match msg {
         NftReceiveMsg::AddNftReward { box_id, collection_addr, nft_id, odds } => {
-            if info.sender.clone() != collection_addr.clone() {
+            let collection_addr = util::validate_addr(deps.api, "collection", &collection_addr)?;
+            // The reward is the token the collection just sent, so it has to be the one named
+            if info.sender != collection_addr || nft_id != wrapper.token_id {
                 return Err(ContractError::InvalidCw721Token {});
             }
 
-            let cfg = CONFIG.load(deps.storage)?;
-
             let fortune_box = BOX_MAP.load(deps.storage, box_id.clone());
 
             match fortune_box {
                 Ok(mut fortune_box) => {
-                    let mut last_reward_id = 0;
-                    if fortune_box.rewards.len() > 0 {
-                        last_reward_id = fortune_box.rewards.last().unwrap().id;
-                    }
-
-                    //sum all odds in fortune_box.rewards
-                    let current_max_odds = fortune_box.rewards
-                        .iter()
-                        .fold(0, |acc, next| { acc + next.odds });
-
-                    if current_max_odds + odds > cfg.max_odds {
-                        return Err(ContractError::MaxOddsReached {
-                            msg: current_max_odds.to_string() + "/" + &odds.to_string(),
-                        });
-                    }
+                    let sender = deps.api.addr_validate(&wrapper.sender)?;
+                    util::check_box_admin(deps.storage, &fortune_box, sender)?;
+                    util::check_draft(&fortune_box)?;
+                    let last_reward_id = util::last_reward_id(deps.storage, &fortune_box)?;
+                    util::check_odds(&fortune_box, odds)?;
 
                     let reward = RewardData {
                         id: last_reward_id + 1,
                         reward_type: crate::msg::RewardType::Nft,
                         collection_addr: Some(collection_addr.clone()),
-                        nft_id: Some(nft_id.clone()),
+                        nft_id: Some(wrapper.token_id.clone()),
                         decimals: None,
-                        denom: None,
-                        amount: None,
+                        asset: None,
                         odds,
                         count: 1,
+                        bundle: vec![],
                     };
 
                     fortune_box.rewards.push(reward);
@@ -203,20 +418,95 @@ pub fn execute_receive_nft(
                     Ok(
                         Response::new()
                             .add_attribute("action", "execute_create_box")
-                            .add_attribute("collection_addr", collection_addr.clone())
-                            .add_attribute("nft_id", nft_id.clone())
-                            .add_attribute("odds", odds.to_string().clone())
+                            .add_attribute("collection_addr", collection_addr)
+                            .add_attribute("nft_id", wrapper.token_id)
+                            .add_attribute("odds", odds.to_string())
                     )
                 }
                 Err(_) => { Err(ContractError::BoxNotFound {}) }
             }
         }
+        NftReceiveMsg::DepositBundle { box_id, bundle_id } => {
+            let sender = deps.api.addr_validate(&wrapper.sender)?;
+            let item = BundleItem::Nft {
+                collection: info.sender.into_string(),
+                token_id: wrapper.token_id,
+            };
+            deposit_bundle(deps.storage, &sender, box_id, bundle_id, vec![item])
+        }
+        NftReceiveMsg::OpenBox { box_id, proof } => {
+            let opener = deps.api.addr_validate(&wrapper.sender)?;
+            let key = KeyUsed::Nft {
+                collection: info.sender.into_string(),
+                token_id: wrapper.token_id,
+            };
+            open_box(deps, env, opener, box_id, OpenPayment::Key(key), proof, None)
+        }
+        NftReceiveMsg::TradeIn { box_id, proof } => {
+            let depositor = deps.api.addr_validate(&wrapper.sender)?;
+            let fortune_box = match BOX_MAP.may_load(deps.storage, box_id.clone())? {
+                Some(fortune_box) => fortune_box,
+                None => {
+                    return Err(ContractError::BoxNotFound {});
+                }
+            };
+            let required = match &fortune_box.trade_in {
+                Some(trade_in) if trade_in.collections.contains(&info.sender.to_string()) =>
+                    trade_in.count,
+                _ => {
+                    return Err(ContractError::InvalidTradeIn {});
+                }
+            };
+            let status = util::box_status(&fortune_box, env.block.time.seconds());
+            if status != BoxStatus::Live {
+                return Err(ContractError::BoxNotLive { status: format!("{:?}", status) });
+            }
+
+            // Deposits wait in escrow until the last one completes the trade-in
+            let key = (box_id.clone(), depositor.clone());
+            let mut deposits = TRADE_IN_DEPOSITS.may_load(deps.storage, key.clone())?.unwrap_or_default();
+            deposits.push(TradedNft { collection: info.sender.into_string(), token_id: wrapper.token_id });
+            if (deposits.len() as u64) < required {
+                TRADE_IN_DEPOSITS.save(deps.storage, key, &deposits)?;
+                return Ok(
+                    Response::new()
+                        .add_attribute("action", "trade_in_deposit")
+                        .add_attribute("box_id", box_id)
+                        .add_attribute("deposited", deposits.len().to_string())
+                        .add_attribute("required", required.to_string())
+                );
+            }
+            TRADE_IN_DEPOSITS.remove(deps.storage, key);
+            open_box(deps, env, depositor, box_id, OpenPayment::TradeIn(deposits), proof, None)
+        }
+    }
+}
+
+pub fn execute_withdraw_trade_in(
+    deps: DepsMut,
+    info: MessageInfo,
+    box_id: String
+) -> Result<Response, ContractError> {
+    let key = (box_id.clone(), info.sender.clone());
+    let deposits = TRADE_IN_DEPOSITS.may_load(deps.storage, key.clone())?.unwrap_or_default();
+    if deposits.is_empty() {
+        return Err(ContractError::NothingToClaim {});
     }
+    TRADE_IN_DEPOSITS.remove(deps.storage, key);
+
+    Ok(
+        Response::new()
+            .add_messages(util::return_nfts(deposits, &info.sender)?)
+            .add_attribute("action", "withdraw_trade_in")
+            .add_attribute("box_id", box_id)
+            .add_attribute("address", info.sender)
+    )
 }
 
 pub fn execute_receive_token(
     deps: DepsMut,
-    _info: MessageInfo,
+    env: Env,
+    info: MessageInfo,
     wrapper: Cw20ReceiveMsg
 ) -> Result<Response, ContractError> {
     let msg: TokenReceiveMsg = from_json(&wrapper.msg)?;
@@ -229,88 +519,170 @@ pub fn execute_receive_token(
             amount,
             odds,
             price,
-            token_denom,
-            token_decimals,
-            token_type,
             duration,
         } => {
             let cfg = CONFIG.load(deps.storage)?;
+            let creator = util::validate_addr(deps.api, "creator", &creator)?;
+
+            // The reward is whatever cw20 sent the hook, so it has to be the one named
+            if denom != info.sender.as_str() {
+                return Err(ContractError::InvalidCw20Token {});
+            }
+            let reward_asset = util::allowed_asset(
+                deps.storage,
+                &(AssetInfo::Cw20 { contract_addr: info.sender.clone() })
+            )?;
+            if wrapper.amount != amount {
+                return Err(ContractError::AmountNotMatch {});
+            }
 
             let fortune_box = BOX_MAP.load(deps.storage, box_id.clone());
 
             match fortune_box {
                 Ok(mut fortune_box) => {
-                    let last_reward_id = fortune_box.rewards.last().unwrap().id;
-
-                    //sum all odds in fortune_box.rewards
-                    let current_max_odds = fortune_box.rewards
-                        .iter()
-                        .fold(0, |acc, next| { acc + next.odds });
-
-                    if current_max_odds + odds > cfg.max_odds {
-                        return Err(ContractError::MaxOddsReached { msg: "max_odds".to_string() });
-                    }
+                    let sender = deps.api.addr_validate(&wrapper.sender)?;
+                    util::check_box_admin(deps.storage, &fortune_box, sender)?;
+                    util::check_draft(&fortune_box)?;
+                    let last_reward_id = util::last_reward_id(deps.storage, &fortune_box)?;
+                    util::check_odds(&fortune_box, odds)?;
 
                     let reward = RewardData {
                         id: last_reward_id + 1,
                         reward_type: crate::msg::RewardType::Cw20,
-                        denom: Some(denom.clone()),
-                        amount: Some(amount.clone()),
-                        decimals: Some(token_decimals.clone()),
+                        asset: Some(Asset { info: reward_asset.info.clone(), amount }),
+                        decimals: Some(reward_asset.decimals),
                         odds,
                         collection_addr: None,
                         nft_id: None,
                         count: 1,
+                        bundle: vec![],
                     };
 
                     fortune_box.rewards.push(reward);
-                    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
-                    return Ok(
+                    util::escrow(deps.storage, &(Asset { info: reward_asset.info, amount }))?;
+                    BOX_MAP.save(deps.storage, box_id, &fortune_box)?;
+                    Ok(
                         Response::new()
                             .add_attribute("action", "execute_create_box")
-                            .add_attribute("denom", denom.clone())
-                            .add_attribute("amount", amount.clone())
-                            .add_attribute("odds", odds.to_string().clone())
-                    );
+                            .add_attribute("denom", denom)
+                            .add_attribute("amount", amount)
+                            .add_attribute("odds", odds.to_string())
+                    )
                 }
                 Err(_) => {
+                    util::validate_box_id(&box_id)?;
+                    util::validate_duration(duration)?;
+                    let price_asset = util::allowed_asset(deps.storage, &price.info)?;
                     let reward = RewardData {
                         id: 1,
-                        reward_type: crate::msg::RewardType::Nft,
+                        reward_type: crate::msg::RewardType::Cw20,
                         collection_addr: None,
                         nft_id: None,
-                        denom: Some(denom.clone()),
-                        amount: Some(amount.clone()),
-                        decimals: Some(token_decimals.clone()),
+                        asset: Some(Asset { info: reward_asset.info.clone(), amount }),
+                        decimals: Some(reward_asset.decimals),
                         odds,
                         count: 1,
+                        bundle: vec![],
                     };
 
-                    let fbox = FortuneBox {
+                    let mut fbox = FortuneBox {
                         id: box_id.clone(),
-                        creator: creator.clone(),
-                        rewards: vec![reward],
-                        max_odds: cfg.max_odds.clone(),
+                        creator,
+                        rewards: vec![],
+                        max_odds: cfg.max_odds,
                         price,
-                        token_denom,
-                        token_decimals,
-                        token_type,
+                        price_decimals: price_asset.decimals,
                         duration,
-                        is_over: false,
-                        is_featured: false,
+                        status: BoxStatus::Draft,
+                        start_time: None,
+                        end_time: None,
+                        resume_at: None,
+                        odds_model: OddsModel::default(),
+                        allow_empty: false,
+                        consolation_points: Uint128::zero(),
+                        opened: 0,
+                        featured_until: None,
+                        allowlist_root: None,
+                        holder_gate: vec![],
+                        open_limits: OpenLimits::default(),
+                        key: None,
+                        held_keys: vec![],
+                        held_key_amount: Uint128::zero(),
+                        kind: BoxKind::Instant,
+                        raffle_pot: Uint128::zero(),
+                        trade_in: None,
+                        reroll: None,
+                        series: None,
                         winners: None,
                     };
-                    BOX_MAP.save(deps.storage, box_id.clone(), &fbox)?;
-                    return Ok(
+                    util::check_odds(&fbox, odds)?;
+                    fbox.rewards.push(reward);
+                    util::escrow(deps.storage, &(Asset { info: reward_asset.info, amount }))?;
+                    BOX_MAP.save(deps.storage, box_id, &fbox)?;
+                    Ok(
                         Response::new()
                             .add_attribute("action", "execute_create_box")
-                            .add_attribute("denom", denom.clone())
-                            .add_attribute("amount", amount.clone())
-                            .add_attribute("odds", odds.to_string().clone())
-                    );
+                            .add_attribute("denom", denom)
+                            .add_attribute("amount", amount)
+                            .add_attribute("odds", odds.to_string())
+                    )
                 }
             }
         }
+        TokenReceiveMsg::TopUpReward { box_id, reward_id, count } => {
+            let sender = deps.api.addr_validate(&wrapper.sender)?;
+            let payment = Asset {
+                info: AssetInfo::Cw20 { contract_addr: info.sender },
+                amount: wrapper.amount,
+            };
+            top_up_reward(
+                deps.storage,
+                &sender,
+                box_id,
+                reward_id,
+                count,
+                RewardType::Cw20,
+                payment
+            )
+        }
+        TokenReceiveMsg::OpenBox { box_id, proof, referrer } => {
+            let opener = deps.api.addr_validate(&wrapper.sender)?;
+            let referrer = match referrer {
+                Some(referrer) => Some(util::validate_addr(deps.api, "referrer", &referrer)?),
+                None => None,
+            };
+            let payment = Asset {
+                info: AssetInfo::Cw20 { contract_addr: info.sender },
+                amount: wrapper.amount,
+            };
+            open_box(deps, env, opener, box_id, OpenPayment::Funds(payment), proof, referrer)
+        }
+        TokenReceiveMsg::DepositBundle { box_id, bundle_id } => {
+            let sender = deps.api.addr_validate(&wrapper.sender)?;
+            let item = BundleItem::Token {
+                asset: Asset {
+                    info: AssetInfo::Cw20 { contract_addr: info.sender },
+                    amount: wrapper.amount,
+                },
+            };
+            deposit_bundle(deps.storage, &sender, box_id, bundle_id, vec![item])
+        }
+        TokenReceiveMsg::Reroll { box_id } => {
+            let opener = deps.api.addr_validate(&wrapper.sender)?;
+            let payment = Asset {
+                info: AssetInfo::Cw20 { contract_addr: info.sender },
+                amount: wrapper.amount,
+            };
+            execute_reroll(deps, env, opener, box_id, payment)
+        }
+        TokenReceiveMsg::OpenBoxWithKey { box_id, proof } => {
+            let opener = deps.api.addr_validate(&wrapper.sender)?;
+            let key = KeyUsed::Cw20 {
+                token: info.sender.into_string(),
+                amount: wrapper.amount,
+            };
+            open_box(deps, env, opener, box_id, OpenPayment::Key(key), proof, None)
+        }
     }
 }
 
@@ -320,316 +692,1815 @@ pub fn execute_add_tokenfactory_rewards(
     box_id: String,
     rewards: Vec<TokenFactoryReward>
 ) -> Result<Response, ContractError> {
-    let cfg = CONFIG.load(deps.storage)?;
-
     let fortune_box = BOX_MAP.load(deps.storage, box_id.clone());
 
     match fortune_box {
         Ok(mut fortune_box) => {
-            let mut last_reward_id = 0;
-            if fortune_box.rewards.len() > 0 {
-                last_reward_id = fortune_box.rewards.last().unwrap().id;
-            }
+            util::check_box_admin(deps.storage, &fortune_box, info.sender.clone())?;
+            util::check_draft(&fortune_box)?;
+            let last_reward_id = util::last_reward_id(deps.storage, &fortune_box)?;
 
-            //sum all odds in fortune_box.rewards
-            let current_max_odds = fortune_box.rewards
+            let rewards_max_odds = rewards
                 .iter()
-                .fold(0, |acc, next| { acc + next.odds });
-
-            let rewards_max_odds = rewards.iter().fold(0, |acc, next| { acc + next.odds });
+                .try_fold(0u64, |acc, next| acc.checked_add(next.odds))
+                .ok_or(ContractError::MaxOddsReached {
+                    msg: format!("overflow/{}", fortune_box.max_odds),
+                })?;
 
             let amount_sum = rewards
                 .iter()
-                .fold(Uint128::zero(), |acc, next| {
-                    acc + Uint128::from(next.amount) * Uint128::from(next.count)
-                });
+                .fold(Uint128::zero(), |acc, next| { acc + next.amount * Uint128::from(next.count) });
 
-            let is_correct_amount = match must_pay(&info, &cfg.native_token.clone()) {
-                Ok(it) => it,
-                Err(_err) => {
-                    return Err(ContractError::PaymentFailed {});
+            util::check_odds(&fortune_box, rewards_max_odds)?;
+
+            for reward in rewards.iter() {
+                let asset = util::allowed_asset(
+                    deps.storage,
+                    &(AssetInfo::Native { denom: reward.token_denom.clone() })
+                )?;
+                if asset.decimals != reward.token_decimals {
+                    return Err(ContractError::DecimalsMismatch { expected: asset.decimals });
                 }
-            };
+            }
 
-            // if is_correct_amount != amount_sum {
-            //     return Err(ContractError::AmountNotMatch {});
-            // }
+            // The deposit has to cover every reward exactly, denom by denom
+            let mut owed: Vec<Coin> = Vec::new();
+            for reward in rewards.iter() {
+                let amount = reward.amount * Uint128::from(reward.count);
+                match owed.iter_mut().find(|coin| coin.denom == reward.token_denom) {
+                    Some(coin) => {
+                        coin.amount += amount;
+                    }
+                    None => owed.push(Coin { denom: reward.token_denom.clone(), amount }),
+                }
+            }
+            if info.funds.len() != owed.len() {
+                return Err(ContractError::PaymentFailed {});
+            }
+            for coin in owed.iter() {
+                match info.funds.iter().find(|paid| paid.denom == coin.denom) {
+                    Some(paid) if paid.amount == coin.amount => {}
+                    Some(_) => {
+                        return Err(ContractError::AmountNotMatch {});
+                    }
+                    None => {
+                        return Err(ContractError::PaymentFailed {});
+                    }
+                }
+            }
 
-            if current_max_odds + rewards_max_odds > cfg.max_odds {
-                return Err(ContractError::MaxOddsReached { msg: "max odds tf".to_string() });
+            for coin in owed {
+                util::escrow(deps.storage, &coin.into())?;
             }
 
-            for reward in rewards.clone() {
+            for (reward_id, reward) in (last_reward_id + 1..).zip(rewards.iter()) {
                 let rwrd = RewardData {
-                    id: last_reward_id + 1,
+                    id: reward_id,
                     reward_type: crate::msg::RewardType::TokenFactory,
-                    denom: Some(reward.token_denom.clone()),
-                    amount: Some(reward.amount.clone()),
-                    decimals: Some(reward.token_decimals.clone()),
-                    odds: reward.odds.clone(),
+                    asset: Some(Asset {
+                        info: AssetInfo::Native { denom: reward.token_denom.clone() },
+                        amount: reward.amount,
+                    }),
+                    decimals: Some(reward.token_decimals),
+                    odds: reward.odds,
                     collection_addr: None,
                     nft_id: None,
                     count: reward.count,
+                    bundle: vec![],
                 };
-                last_reward_id += 1;
                 fortune_box.rewards.push(rwrd);
             }
 
-            BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
-            return Ok(
+            BOX_MAP.save(deps.storage, box_id, &fortune_box)?;
+            Ok(
                 Response::new()
                     .add_attribute("action", "execute_add_rewards")
-                    .add_attribute("totals", rewards.clone().iter().len().to_string())
-                    .add_attribute("amount_send", is_correct_amount.to_string())
+                    .add_attribute("totals", rewards.len().to_string())
                     .add_attribute("total_amount", amount_sum.to_string())
-                    .add_attribute("odds", rewards_max_odds.to_string().clone())
-            );
-        }
-        Err(_) => {
-            return Err(ContractError::BoxNotFound {});
+                    .add_attribute("odds", rewards_max_odds.to_string())
+            )
         }
+        Err(_) => { Err(ContractError::BoxNotFound {}) }
     }
 }
 
-pub fn execute_cancel_box(
+pub fn execute_add_bundle_reward(
     deps: DepsMut,
     info: MessageInfo,
-    box_id: String
+    box_id: String,
+    odds: u64,
+    items: Vec<BundleItem>
 ) -> Result<Response, ContractError> {
-    let fortune_box = BOX_MAP.load(deps.storage, box_id.clone());
-    match fortune_box {
-        Ok(fortune_box) => {
-            let mut msgs = Vec::new();
-            for reward in fortune_box.rewards.iter() {
-                if reward.reward_type == RewardType::Nft {
-                    let msg = CosmosMsg::Wasm(WasmMsg::Execute {
-                        contract_addr: reward.collection_addr.clone().unwrap().to_string().clone(),
-                        msg: to_json_binary(
-                            &(Cw721ExecuteMsg::TransferNft {
-                                token_id: reward.nft_id.clone().unwrap(),
-                                recipient: info.sender.to_string().clone(),
-                            })
-                        )?,
-                        funds: vec![],
-                    });
-
-                    msgs.push(msg);
-                } else if reward.reward_type == RewardType::TokenFactory {
-                    let msg = util::transfer_token_message(
-                        reward.denom.clone().unwrap(),
-                        "native".to_string(),
-                        reward.amount.unwrap() * Uint128::from(reward.count),
-                        info.sender.clone()
-                    )?;
+    let fortune_box = load_editable_box(deps.storage, box_id.clone(), &info.sender)?;
+    util::check_odds(&fortune_box, odds)?;
+    let items = util::validate_bundle(deps.storage, deps.api, items)?;
 
-                    msgs.push(msg);
-                }
-            }
+    // The id is reserved now so the bundle keeps it once complete
+    let bundle_id = util::last_reward_id(deps.storage, &fortune_box)? + 1;
+    let bundle = PendingBundle {
+        deposited: vec![false; items.len()],
+        reward: RewardData {
+            id: bundle_id,
+            reward_type: RewardType::Bundle,
+            collection_addr: None,
+            nft_id: None,
+            asset: None,
+            decimals: None,
+            odds,
+            count: 1,
+            bundle: items,
+        },
+    };
+    PENDING_BUNDLES.save(deps.storage, (box_id.clone(), bundle_id), &bundle)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "add_bundle_reward")
+            .add_attribute("box_id", box_id)
+            .add_attribute("bundle_id", bundle_id.to_string())
+            .add_attribute("items", bundle.deposited.len().to_string())
+            .add_attribute("odds", odds.to_string())
+    )
+}
 
-            BOX_MAP.remove(deps.storage, fortune_box.id.clone());
-            Ok(Response::new().add_messages(msgs).add_attribute("action", "cancel_fortune_box"))
+/// Marks `items` as deposited into the bundle, which joins the box rewards once complete.
+fn deposit_bundle(
+    storage: &mut dyn Storage,
+    sender: &Addr,
+    box_id: String,
+    bundle_id: u64,
+    items: Vec<BundleItem>
+) -> Result<Response, ContractError> {
+    let mut fortune_box = load_editable_box(storage, box_id.clone(), sender)?;
+    let key = (box_id.clone(), bundle_id);
+    let mut bundle = match PENDING_BUNDLES.may_load(storage, key.clone())? {
+        Some(bundle) => bundle,
+        None => {
+            return Err(ContractError::BundleNotFound {});
         }
-        Err(_) => { Err(ContractError::BoxNotFound {}) }
+    };
+    for item in items.iter() {
+        util::deposit_bundle_item(&mut bundle, item)?;
+        if let BundleItem::Token { asset } = item {
+            util::escrow(storage, asset)?;
+        }
+    }
+
+    let remaining = bundle.deposited
+        .iter()
+        .filter(|deposited| !**deposited)
+        .count();
+    if remaining > 0 {
+        PENDING_BUNDLES.save(storage, key, &bundle)?;
+    } else {
+        util::check_odds(&fortune_box, bundle.reward.odds)?;
+        PENDING_BUNDLES.remove(storage, key);
+        fortune_box.rewards.push(bundle.reward);
+        BOX_MAP.save(storage, box_id.clone(), &fortune_box)?;
     }
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "deposit_bundle")
+            .add_attribute("box_id", box_id)
+            .add_attribute("bundle_id", bundle_id.to_string())
+            .add_attribute("remaining", remaining.to_string())
+    )
 }
 
-pub fn execute_open_box(
+pub fn execute_cancel_bundle(
     deps: DepsMut,
-    env: Env,
     info: MessageInfo,
-    box_id: String
+    box_id: String,
+    bundle_id: u64
 ) -> Result<Response, ContractError> {
-    let fortune_box = BOX_MAP.load(deps.storage, box_id.clone());
-    let cfg = CONFIG.load(deps.storage)?;
-    match fortune_box {
-        Ok(mut fortune_box) => {
-            if fortune_box.is_over {
-                return Err(ContractError::BoxTerminated {});
-            }
-            let mut weighted_list = Vec::new();
-            for ticket_info in &fortune_box.rewards {
-                for _ in 0..ticket_info.odds {
-                    weighted_list.push(ticket_info.id.clone());
-                }
-            }
+    let fortune_box = load_editable_box(deps.storage, box_id.clone(), &info.sender)?;
+    let key = (box_id.clone(), bundle_id);
+    let bundle = match PENDING_BUNDLES.may_load(deps.storage, key.clone())? {
+        Some(bundle) => bundle,
+        None => {
+            return Err(ContractError::BundleNotFound {});
+        }
+    };
+    PENDING_BUNDLES.remove(deps.storage, key);
 
-            let mut hasher = Sha256::new();
-            hasher.update(env.block.time.seconds().to_string());
-            let result = hasher.finalize();
+    Ok(
+        Response::new()
+            .add_messages(util::release_bundle(deps.storage, &bundle, fortune_box.creator)?)
+            .add_attribute("action", "cancel_bundle")
+            .add_attribute("box_id", box_id)
+            .add_attribute("bundle_id", bundle_id.to_string())
+    )
+}
 
-            // Manually convert the first 8 bytes of the hash into a u64
-            let mut bytes = [0u8; 8];
-            bytes.copy_from_slice(&result[0..8]);
-            let random_number = u64::from_be_bytes(bytes) % (weighted_list.len() as u64);
-            Uint128::new(random_number as u128);
+/// Loads a box whose rewards `sender` may still edit.
+fn load_editable_box(
+    storage: &dyn Storage,
+    box_id: String,
+    sender: &Addr
+) -> Result<FortuneBox, ContractError> {
+    let fortune_box = match BOX_MAP.load(storage, box_id) {
+        Ok(fortune_box) => fortune_box,
+        Err(_) => {
+            return Err(ContractError::BoxNotFound {});
+        }
+    };
+    if fortune_box.creator != *sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    util::check_draft(&fortune_box)?;
+    Ok(fortune_box)
+}
 
-            let reward_id = weighted_list[random_number as usize].clone();
-            //find the reward with id == winner_id
+pub fn execute_update_reward_odds(
+    deps: DepsMut,
+    info: MessageInfo,
+    box_id: String,
+    reward_id: u64,
+    odds: u64
+) -> Result<Response, ContractError> {
+    let mut fortune_box = load_editable_box(deps.storage, box_id.clone(), &info.sender)?;
+    let index = match fortune_box.rewards.iter().position(|x| x.id == reward_id) {
+        Some(index) => index,
+        None => {
+            return Err(ContractError::RewardNotFound {});
+        }
+    };
 
-            let index = fortune_box.rewards
-                .iter()
-                .position(|x| x.id == reward_id)
-                .unwrap();
-
-            if let Some(reward) = fortune_box.rewards.get_mut(index) {
-                let mut msgs = Vec::new();
-                if reward.reward_type == RewardType::TokenFactory {
-                    msgs.push(
-                        util::transfer_token_message(
-                            reward.denom.clone().unwrap(),
-                            "native".to_string(),
-                            reward.amount.unwrap(),
-                            info.sender.clone()
-                        )?
-                    );
-                } else if reward.reward_type == RewardType::Nft {
-                    msgs.push(
-                        CosmosMsg::Wasm(WasmMsg::Execute {
-                            contract_addr: reward.collection_addr
-                                .clone()
-                                .unwrap()
-                                .to_string()
-                                .clone(),
-                            msg: to_json_binary(
-                                &(Cw721ExecuteMsg::TransferNft {
-                                    token_id: reward.clone().nft_id.unwrap().clone(),
-                                    recipient: info.sender.clone().into(),
-                                })
-                            )?,
-                            funds: vec![],
-                        })
-                    );
-                }
+    let previous_odds = fortune_box.rewards[index].odds;
+    fortune_box.rewards[index].odds = 0;
+    util::check_odds(&fortune_box, odds)?;
+    fortune_box.rewards[index].odds = odds;
 
-                let fees: Uint128 =
-                    (info.funds[0].amount * Uint128::from(5u64)) / Uint128::from(100u64);
-                msgs.push(
-                    util::transfer_token_message(
-                        info.funds[0].denom.clone(),
-                        "native".to_string(),
-                        info.funds[0].amount - fees,
-                        fortune_box.creator.clone()
-                    )?
-                );
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+    Ok(
+        Response::new()
+            .add_attribute("action", "update_reward_odds")
+            .add_attribute("box_id", box_id)
+            .add_attribute("reward_id", reward_id.to_string())
+            .add_attribute("previous_odds", previous_odds.to_string())
+            .add_attribute("odds", odds.to_string())
+    )
+}
 
-                msgs.push(
-                    util::transfer_token_message(
-                        info.funds[0].denom.clone(),
-                        "native".to_string(),
-                        fees,
-                        cfg.injscribed_address.clone()
-                    )?
-                );
+pub fn execute_remove_reward(
+    deps: DepsMut,
+    info: MessageInfo,
+    box_id: String,
+    reward_id: u64
+) -> Result<Response, ContractError> {
+    let mut fortune_box = load_editable_box(deps.storage, box_id.clone(), &info.sender)?;
+    let index = match fortune_box.rewards.iter().position(|x| x.id == reward_id) {
+        Some(index) => index,
+        None => {
+            return Err(ContractError::RewardNotFound {});
+        }
+    };
 
-                // Directly modify the 'count' of the reward
-                reward.count -= 1;
-                let userinfo = ACCOUNT_MAP.load(deps.storage, info.sender.clone());
-                match userinfo {
-                    Ok(mut userinfo) => {
-                        userinfo.box_opened += 1;
-                        userinfo.rewards
-                            .entry(box_id.clone())
-                            .or_insert_with(|| vec![reward_id.clone()])
-                            .push(reward_id.clone());
-                        userinfo.inj_spent += fortune_box.price.clone();
-                        ACCOUNT_MAP.save(deps.storage, info.sender.clone(), &userinfo)?;
-                    }
-                    Err(_) => {
-                        let mut map: HashMap<String, Vec<u64>> = HashMap::new();
-                        map.insert(box_id.clone(), vec![reward_id.clone()]);
-                        let usr: UserInfo = UserInfo {
-                            address: info.sender.clone(),
-                            box_created: 0,
-                            inj_spent: fortune_box.price.clone(),
-                            tokens_spent: Uint128::from(0u128),
-                            box_opened: 1,
-                            rewards: map,
-                        };
-
-                        ACCOUNT_MAP.save(deps.storage, info.sender.clone(), &usr)?;
-                    }
-                }
+    let reward = fortune_box.rewards.remove(index);
+    let msgs = util::reward_transfer_messages(
+        deps.storage,
+        &reward,
+        reward.count,
+        fortune_box.creator.clone()
+    )?;
 
-                if reward.count == 0 {
-                    fortune_box.is_over = true;
-                    for reward in fortune_box.rewards.clone() {
-                        if reward.count == 0 {
-                            continue;
-                        }
-                        if reward.reward_type == RewardType::TokenFactory {
-                            msgs.push(
-                                util::transfer_token_message(
-                                    reward.denom.clone().unwrap(),
-                                    "native".to_string(),
-                                    reward.amount.unwrap() * Uint128::from(reward.count),
-                                    fortune_box.creator.clone()
-                                )?
-                            );
-                        } else if reward.reward_type == RewardType::Nft {
-                            msgs.push(
-                                CosmosMsg::Wasm(WasmMsg::Execute {
-                                    contract_addr: reward.collection_addr
-                                        .unwrap()
-                                        .to_string()
-                                        .clone(),
-                                    msg: to_json_binary(
-                                        &(Cw721ExecuteMsg::TransferNft {
-                                            token_id: reward.nft_id.unwrap().clone(),
-                                            recipient: fortune_box.creator.clone().into(),
-                                        })
-                                    )?,
-                                    funds: vec![],
-                                })
-                            );
-                        }
-                    }
-                }
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+    Ok(
+        Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "remove_reward")
+            .add_attribute("box_id", box_id)
+            .add_attribute("reward_id", reward_id.to_string())
+            .add_attribute("count", reward.count.to_string())
+    )
+}
 
-                BOX_MAP.save(deps.storage, box_id, &fortune_box)?;
-                Ok(
-                    Response::new()
-                        .add_messages(msgs)
-                        .add_attribute("action", "execute_open_box")
-                        .add_attribute("seed", random_number.to_string().clone())
-                        .add_attribute("reward_id", reward_id.to_string().clone())
-                )
-            } else {
-                return Err(ContractError::RewardNotFound {});
-            }
+pub fn execute_top_up_reward(
+    deps: DepsMut,
+    info: MessageInfo,
+    box_id: String,
+    reward_id: u64,
+    count: u64
+) -> Result<Response, ContractError> {
+    let payment = match one_coin(&info) {
+        Ok(payment) => payment,
+        Err(_) => {
+            return Err(ContractError::PaymentFailed {});
+        }
+    };
+    top_up_reward(
+        deps.storage,
+        &info.sender,
+        box_id,
+        reward_id,
+        count,
+        RewardType::TokenFactory,
+        payment.into()
+    )
+}
+
+/// Adds `count` units to a reward once the deposit matches `count` times its amount.
+fn top_up_reward(
+    storage: &mut dyn Storage,
+    sender: &Addr,
+    box_id: String,
+    reward_id: u64,
+    count: u64,
+    reward_type: RewardType,
+    payment: Asset
+) -> Result<Response, ContractError> {
+    let mut fortune_box = load_editable_box(storage, box_id.clone(), sender)?;
+    let reward = match fortune_box.rewards.iter_mut().find(|x| x.id == reward_id) {
+        Some(reward) => reward,
+        None => {
+            return Err(ContractError::RewardNotFound {});
+        }
+    };
+
+    let reward_asset = match &reward.asset {
+        Some(reward_asset) => reward_asset,
+        None => {
+            return Err(ContractError::InvalidTopUp {});
         }
+    };
+    if count == 0 || reward.reward_type != reward_type || reward_asset.info != payment.info {
+        return Err(ContractError::InvalidTopUp {});
+    }
+    if payment.amount != reward_asset.amount * Uint128::from(count) {
+        return Err(ContractError::AmountNotMatch {});
+    }
+    reward.count += count;
+    let total = reward.count;
+    util::escrow(storage, &payment)?;
+
+    BOX_MAP.save(storage, box_id.clone(), &fortune_box)?;
+    Ok(
+        Response::new()
+            .add_attribute("action", "top_up_reward")
+            .add_attribute("box_id", box_id)
+            .add_attribute("reward_id", reward_id.to_string())
+            .add_attribute("added", count.to_string())
+            .add_attribute("count", total.to_string())
+    )
+}
+
+pub fn execute_publish_box(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    box_id: String,
+    start_time: Option<u64>
+) -> Result<Response, ContractError> {
+    let mut fortune_box = match BOX_MAP.load(deps.storage, box_id.clone()) {
+        Ok(fortune_box) => fortune_box,
         Err(_) => {
             return Err(ContractError::BoxNotFound {});
         }
+    };
+    if fortune_box.creator != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if util::has_pending_bundles(deps.storage, box_id.clone()) {
+        return Err(ContractError::BundleIncomplete {});
+    }
+    if util::draw_outcome(&fortune_box, &[]).is_none() {
+        return Err(ContractError::EmptyBox {});
     }
-}
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetConfig {} => to_json_binary(&query_config(deps)?),
-        QueryMsg::GetBoxes {} => to_json_binary(&query_boxes(deps)?),
-        QueryMsg::GetUsers {} => to_json_binary(&query_users(deps)?),
+    let now = env.block.time.seconds();
+    let start_time = start_time.unwrap_or(now).max(now);
+    let status = if start_time > now { BoxStatus::Scheduled } else { BoxStatus::Live };
+    util::check_transition(&fortune_box.status, &status)?;
+
+    fortune_box.status = status.clone();
+    fortune_box.start_time = Some(start_time);
+    if fortune_box.duration > 0 {
+        let end_time = start_time.checked_add(fortune_box.duration);
+        fortune_box.end_time = Some(end_time.ok_or(ContractError::InvalidStartTime {})?);
     }
-}
 
-pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
-    let config: Config = CONFIG.load(deps.storage)?;
-    Ok(ConfigResponse {
-        owner: config.owner.clone(),
-        token: config.native_token,
-        feature_fees: config.feature_fees,
-        injscribed_address: config.injscribed_address,
-    })
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+    Ok(
+        Response::new()
+            .add_attribute("action", "publish_box")
+            .add_attribute("box_id", box_id)
+            .add_attribute("status", format!("{:?}", status))
+            .add_attribute("start_time", start_time.to_string())
+    )
 }
 
-pub fn query_boxes(deps: Deps) -> StdResult<BoxesResponse> {
-    let boxes: StdResult<Vec<FortuneBox>> = BOX_MAP.range(
+pub fn execute_pause_box(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    box_id: String,
+    resume_at: Option<u64>
+) -> Result<Response, ContractError> {
+    let mut fortune_box = match BOX_MAP.load(deps.storage, box_id.clone()) {
+        Ok(fortune_box) => fortune_box,
+        Err(_) => {
+            return Err(ContractError::BoxNotFound {});
+        }
+    };
+    util::check_box_admin(deps.storage, &fortune_box, info.sender.clone())?;
+    let status = util::box_status(&fortune_box, env.block.time.seconds());
+    util::check_transition(&status, &BoxStatus::Paused)?;
+    if resume_at.is_some_and(|resume_at| resume_at <= env.block.time.seconds()) {
+        return Err(ContractError::InvalidResumeTime {});
+    }
+
+    fortune_box.status = BoxStatus::Paused;
+    fortune_box.resume_at = resume_at;
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+
+    let event = Event::new("box_paused")
+        .add_attribute("box_id", box_id.clone())
+        .add_attribute("by", info.sender)
+        .add_attribute("resume_at", resume_at.map_or("none".to_string(), |x| x.to_string()));
+    Ok(
+        Response::new()
+            .add_event(event)
+            .add_attribute("action", "pause_box")
+            .add_attribute("box_id", box_id)
+    )
+}
+
+pub fn execute_resume_box(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    box_id: String
+) -> Result<Response, ContractError> {
+    let mut fortune_box = match BOX_MAP.load(deps.storage, box_id.clone()) {
+        Ok(fortune_box) => fortune_box,
+        Err(_) => {
+            return Err(ContractError::BoxNotFound {});
+        }
+    };
+    util::check_box_admin(deps.storage, &fortune_box, info.sender.clone())?;
+    let status = util::box_status(&fortune_box, env.block.time.seconds());
+    util::check_transition(&status, &BoxStatus::Live)?;
+
+    fortune_box.status = BoxStatus::Live;
+    fortune_box.resume_at = None;
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+
+    let event = Event::new("box_resumed")
+        .add_attribute("box_id", box_id.clone())
+        .add_attribute("by", info.sender);
+    Ok(
+        Response::new()
+            .add_event(event)
+            .add_attribute("action", "resume_box")
+            .add_attribute("box_id", box_id)
+    )
+}
+
+pub fn execute_feature_box(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    box_id: String,
+    slots: u64
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let mut fortune_box = match BOX_MAP.load(deps.storage, box_id.clone()) {
+        Ok(fortune_box) => fortune_box,
+        Err(_) => {
+            return Err(ContractError::BoxNotFound {});
+        }
+    };
+    util::check_box_admin(deps.storage, &fortune_box, info.sender.clone())?;
+
+    let now = env.block.time.seconds();
+    match util::box_status(&fortune_box, now) {
+        BoxStatus::SoldOut | BoxStatus::Cancelled | BoxStatus::Expired => {
+            return Err(ContractError::BoxTerminated {});
+        }
+        _ => {}
+    }
+
+    let (duration, fees) = match
+        (
+            slots.checked_mul(cfg.feature_period),
+            cfg.feature_fees.checked_mul(Uint128::from(slots)),
+        )
+    {
+        (Some(duration), Ok(fees)) => (duration, fees),
+        _ => {
+            return Err(ContractError::InvalidSlots {});
+        }
+    };
+    let paid = match must_pay(&info, &cfg.native_token) {
+        Ok(it) => it,
+        Err(_err) => {
+            return Err(ContractError::PaymentFailed {});
+        }
+    };
+    if slots == 0 || paid != fees {
+        return Err(ContractError::AmountNotMatch {});
+    }
+
+    // Drop lapsed listings so only live ones count against the slot limit
+    let featured: Vec<(String, u64)> = FEATURED.range(
+        deps.storage,
+        None,
+        None,
+        Order::Ascending
+    ).collect::<StdResult<_>>()?;
+    let mut taken = 0;
+    for (featured_id, featured_until) in featured {
+        if featured_until <= now {
+            FEATURED.remove(deps.storage, featured_id);
+        } else if featured_id != box_id {
+            taken += 1;
+        }
+    }
+
+    let current = fortune_box.featured_until.filter(|until| *until > now);
+    if current.is_none() && taken >= cfg.max_featured {
+        return Err(ContractError::FeaturedSlotsFull {});
+    }
+
+    let featured_until = mark_featured(deps.storage, &mut fortune_box, now, duration)?;
+
+    util::accrue_fee(deps.storage, cfg.native_token, paid)?;
+    Ok(
+        Response::new()
+            .add_attribute("action", "feature_box")
+            .add_attribute("box_id", box_id)
+            .add_attribute("slots", slots.to_string())
+            .add_attribute("featured_until", featured_until.to_string())
+    )
+}
+
+/// Features the box for `duration` more seconds, extending a running period.
+fn mark_featured(
+    storage: &mut dyn Storage,
+    fortune_box: &mut FortuneBox,
+    now: u64,
+    duration: u64
+) -> Result<u64, ContractError> {
+    let current = fortune_box.featured_until.filter(|until| *until > now);
+    let featured_until = match current.unwrap_or(now).checked_add(duration) {
+        Some(featured_until) => featured_until,
+        None => {
+            return Err(ContractError::InvalidSlots {});
+        }
+    };
+    fortune_box.featured_until = Some(featured_until);
+    FEATURED.save(storage, fortune_box.id.clone(), &featured_until)?;
+    BOX_MAP.save(storage, fortune_box.id.clone(), fortune_box)?;
+    Ok(featured_until)
+}
+
+pub fn execute_open_feature_auction(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    slots: u64,
+    min_bid: Uint128,
+    duration: u64,
+    feature_duration: u64
+) -> Result<Response, ContractError> {
+    util::check_owner(deps.storage, info.sender)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    util::validate_duration(duration)?;
+    util::validate_duration(feature_duration)?;
+    let end_time = match env.block.time.seconds().checked_add(duration) {
+        Some(end_time) => end_time,
+        None => {
+            return Err(ContractError::InvalidDuration { max: util::MAX_DURATION });
+        }
+    };
+
+    let id = AUCTION_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    let auction = FeatureAuction {
+        id,
+        slots,
+        denom: cfg.native_token,
+        min_bid,
+        end_time,
+        feature_duration,
+        settled: false,
+    };
+    AUCTION_COUNT.save(deps.storage, &id)?;
+    AUCTIONS.save(deps.storage, id, &auction)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "open_feature_auction")
+            .add_attribute("auction_id", id.to_string())
+            .add_attribute("slots", slots.to_string())
+            .add_attribute("end_time", auction.end_time.to_string())
+    )
+}
+
+pub fn execute_bid_feature_auction(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    auction_id: u64,
+    box_id: String
+) -> Result<Response, ContractError> {
+    let auction = match AUCTIONS.load(deps.storage, auction_id) {
+        Ok(auction) => auction,
+        Err(_) => {
+            return Err(ContractError::AuctionNotFound {});
+        }
+    };
+    if auction.settled || env.block.time.seconds() >= auction.end_time {
+        return Err(ContractError::AuctionClosed {});
+    }
+    let fortune_box = match BOX_MAP.load(deps.storage, box_id.clone()) {
+        Ok(fortune_box) => fortune_box,
+        Err(_) => {
+            return Err(ContractError::BoxNotFound {});
+        }
+    };
+    util::check_box_admin(deps.storage, &fortune_box, info.sender.clone())?;
+    let amount = match must_pay(&info, &auction.denom) {
+        Ok(it) => it,
+        Err(_err) => {
+            return Err(ContractError::PaymentFailed {});
+        }
+    };
+    if amount < auction.min_bid {
+        return Err(ContractError::BidTooLow { min: auction.min_bid });
+    }
+
+    // Raising a bid replaces it, otherwise a full round pushes out its lowest bid
+    let mut outbid: Option<FeatureBid> = None;
+    if let Some(previous) = AUCTION_BIDS.may_load(deps.storage, (auction_id, box_id.clone()))? {
+        if amount <= previous.amount {
+            return Err(ContractError::BidTooLow { min: previous.amount + Uint128::one() });
+        }
+        outbid = Some(previous);
+    } else {
+        let bids = auction_bids(deps.storage, auction_id)?;
+        if bids.len() as u64 >= auction.slots {
+            match bids.last() {
+                Some(lowest) if amount > lowest.amount => {
+                    AUCTION_BIDS.remove(deps.storage, (auction_id, lowest.box_id.clone()));
+                    outbid = Some(lowest.clone());
+                }
+                Some(lowest) => {
+                    return Err(ContractError::BidTooLow { min: lowest.amount + Uint128::one() });
+                }
+                None => {
+                    return Err(ContractError::AuctionClosed {});
+                }
+            }
+        }
+    }
+
+    let bid = FeatureBid { box_id: box_id.clone(), bidder: info.sender.clone(), amount };
+    AUCTION_BIDS.save(deps.storage, (auction_id, box_id.clone()), &bid)?;
+    let bid_asset = |amount: Uint128| Asset {
+        info: AssetInfo::Native { denom: auction.denom.clone() },
+        amount,
+    };
+    util::escrow(deps.storage, &bid_asset(amount))?;
+
+    let mut msgs = Vec::new();
+    if let Some(outbid) = outbid {
+        util::release_escrow(deps.storage, &bid_asset(outbid.amount))?;
+        msgs.push(util::transfer_token_message(bid_asset(outbid.amount), outbid.bidder)?);
+    }
+    Ok(
+        Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "bid_feature_auction")
+            .add_attribute("auction_id", auction_id.to_string())
+            .add_attribute("box_id", box_id)
+            .add_attribute("amount", amount)
+    )
+}
+
+pub fn execute_settle_feature_auction(
+    deps: DepsMut,
+    env: Env,
+    auction_id: u64
+) -> Result<Response, ContractError> {
+    let mut auction = match AUCTIONS.load(deps.storage, auction_id) {
+        Ok(auction) => auction,
+        Err(_) => {
+            return Err(ContractError::AuctionNotFound {});
+        }
+    };
+    let now = env.block.time.seconds();
+    if auction.settled {
+        return Err(ContractError::AuctionClosed {});
+    }
+    if now < auction.end_time {
+        return Err(ContractError::AuctionRunning {});
+    }
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let mut taken = FEATURED.range(deps.storage, None, None, Order::Ascending)
+        .filter(|item| item.as_ref().map_or(true, |(_, until)| *until > now))
+        .collect::<StdResult<Vec<_>>>()?
+        .len() as u64;
+
+    let mut msgs = Vec::new();
+    let mut proceeds = Uint128::zero();
+    for bid in auction_bids(deps.storage, auction_id)? {
+        // Every bid leaves escrow, either refunded or as proceeds
+        let amount = Asset { info: AssetInfo::Native { denom: auction.denom.clone() }, amount: bid.amount };
+        util::release_escrow(deps.storage, &amount)?;
+        let mut fortune_box = BOX_MAP.load(deps.storage, bid.box_id.clone())?;
+        let listed = fortune_box.featured_until.is_some_and(|until| until > now);
+        let ended = matches!(
+            util::box_status(&fortune_box, now),
+            BoxStatus::SoldOut | BoxStatus::Cancelled | BoxStatus::Expired
+        );
+        // Boxes that ended while the auction ran, or find every slot taken, get their bid back
+        if ended || (!listed && taken >= cfg.max_featured) {
+            msgs.push(util::transfer_token_message(amount, bid.bidder)?);
+            continue;
+        }
+        if !listed {
+            taken += 1;
+        }
+        mark_featured(deps.storage, &mut fortune_box, now, auction.feature_duration)?;
+        proceeds += bid.amount;
+    }
+    util::accrue_fee(deps.storage, auction.denom.clone(), proceeds)?;
+
+    auction.settled = true;
+    AUCTIONS.save(deps.storage, auction_id, &auction)?;
+    Ok(
+        Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "settle_feature_auction")
+            .add_attribute("auction_id", auction_id.to_string())
+            .add_attribute("proceeds", proceeds)
+    )
+}
+
+/// Bids of an auction, highest first.
+fn auction_bids(storage: &dyn Storage, auction_id: u64) -> StdResult<Vec<FeatureBid>> {
+    let mut bids: Vec<FeatureBid> = AUCTION_BIDS.prefix(auction_id)
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, v)| v))
+        .collect::<StdResult<_>>()?;
+    bids.sort_by_key(|bid| std::cmp::Reverse(bid.amount));
+    Ok(bids)
+}
+
+pub fn execute_cancel_box(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    box_id: String
+) -> Result<Response, ContractError> {
+    let fortune_box = BOX_MAP.load(deps.storage, box_id.clone());
+    match fortune_box {
+        Ok(mut fortune_box) => {
+            util::check_box_admin(deps.storage, &fortune_box, info.sender.clone())?;
+            let status = util::box_status(&fortune_box, env.block.time.seconds());
+            util::check_transition(&status, &BoxStatus::Cancelled)?;
+
+            // Leftover rewards go back to the creator, the box stays around as history.
+            // Series stock stays with the series for its other boxes.
+            let mut msgs = Vec::new();
+            let shared = fortune_box.series.is_some();
+            for reward in fortune_box.rewards.iter_mut().filter(|_| !shared) {
+                msgs.extend(
+                    util::reward_transfer_messages(
+                        deps.storage,
+                        reward,
+                        reward.count,
+                        fortune_box.creator.clone()
+                    )?
+                );
+                reward.count = 0;
+            }
+            let bundles = PENDING_BUNDLES.prefix(box_id.clone())
+                .range(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for (bundle_id, bundle) in bundles {
+                PENDING_BUNDLES.remove(deps.storage, (box_id.clone(), bundle_id));
+                msgs.extend(util::release_bundle(deps.storage, &bundle, fortune_box.creator.clone())?);
+            }
+            msgs.extend(util::release_keys(deps.storage, &mut fortune_box)?);
+            msgs.extend(util::refund_tickets(deps.storage, &mut fortune_box)?);
+            fortune_box.status = BoxStatus::Cancelled;
+            fortune_box.resume_at = None;
+            util::unfeature(deps.storage, &mut fortune_box);
+
+            BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+            Ok(
+                Response::new()
+                    .add_messages(msgs)
+                    .add_attribute("action", "cancel_fortune_box")
+                    .add_attribute("box_id", box_id)
+                    .add_attribute("previous_status", format!("{:?}", status))
+            )
+        }
+        Err(_) => { Err(ContractError::BoxNotFound {}) }
+    }
+}
+
+pub fn execute_create_series(
+    deps: DepsMut,
+    info: MessageInfo,
+    series_id: String,
+    box_id: String
+) -> Result<Response, ContractError> {
+    util::validate_box_id(&series_id)?;
+    if SERIES.has(deps.storage, series_id.clone()) {
+        return Err(ContractError::InvalidSeries {});
+    }
+    let mut fortune_box = load_editable_box(deps.storage, box_id.clone(), &info.sender)?;
+    if fortune_box.kind != BoxKind::Instant || fortune_box.rewards.is_empty() {
+        return Err(ContractError::InvalidSeries {});
+    }
+    if util::has_pending_bundles(deps.storage, box_id.clone()) {
+        return Err(ContractError::BundleIncomplete {});
+    }
+
+    let series = Series {
+        id: series_id.clone(),
+        creator: fortune_box.creator.clone(),
+        rewards: fortune_box.rewards.clone(),
+        boxes: vec![box_id.clone()],
+    };
+    fortune_box.series = Some(series_id.clone());
+    SERIES.save(deps.storage, series_id.clone(), &series)?;
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "create_series")
+            .add_attribute("series_id", series_id)
+            .add_attribute("box_id", box_id)
+            .add_attribute("rewards", series.rewards.len().to_string())
+    )
+}
+
+pub fn execute_join_series(
+    deps: DepsMut,
+    info: MessageInfo,
+    series_id: String,
+    box_id: String,
+    odds: Vec<SeriesOdds>
+) -> Result<Response, ContractError> {
+    let mut series = match SERIES.may_load(deps.storage, series_id.clone())? {
+        Some(series) => series,
+        None => {
+            return Err(ContractError::SeriesNotFound {});
+        }
+    };
+    let mut fortune_box = match BOX_MAP.may_load(deps.storage, box_id.clone())? {
+        Some(fortune_box) => fortune_box,
+        None => {
+            return Err(ContractError::BoxNotFound {});
+        }
+    };
+    if fortune_box.creator != info.sender || series.creator != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if fortune_box.status != BoxStatus::Draft {
+        return Err(ContractError::BoxLocked {});
+    }
+    match &fortune_box.series {
+        Some(joined) if *joined != series_id => {
+            return Err(ContractError::InvalidSeries {});
+        }
+        None if !fortune_box.rewards.is_empty() || fortune_box.kind != BoxKind::Instant => {
+            return Err(ContractError::InvalidSeries {});
+        }
+        _ => {}
+    }
+    if util::has_pending_bundles(deps.storage, box_id.clone()) {
+        return Err(ContractError::BundleIncomplete {});
+    }
+
+    fortune_box.rewards = util::series_rewards(&series, &odds)?;
+    util::check_odds(&fortune_box, 0)?;
+    fortune_box.series = Some(series_id.clone());
+    if !series.boxes.contains(&box_id) {
+        series.boxes.push(box_id.clone());
+        SERIES.save(deps.storage, series_id.clone(), &series)?;
+    }
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "join_series")
+            .add_attribute("series_id", series_id)
+            .add_attribute("box_id", box_id)
+            .add_attribute("overrides", odds.len().to_string())
+    )
+}
+
+pub fn execute_close_series(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    series_id: String
+) -> Result<Response, ContractError> {
+    let mut series = match SERIES.may_load(deps.storage, series_id.clone())? {
+        Some(series) => series,
+        None => {
+            return Err(ContractError::SeriesNotFound {});
+        }
+    };
+    if series.creator != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    let now = env.block.time.seconds();
+    for box_id in series.boxes.iter() {
+        let member = BOX_MAP.load(deps.storage, box_id.clone())?;
+        match util::box_status(&member, now) {
+            BoxStatus::Draft | BoxStatus::SoldOut | BoxStatus::Cancelled | BoxStatus::Expired => {}
+            _ => {
+                return Err(ContractError::SeriesOpen {});
+            }
+        }
+    }
+
+    let mut msgs = Vec::new();
+    for reward in series.rewards.iter_mut() {
+        msgs.extend(
+            util::reward_transfer_messages(deps.storage, reward, reward.count, series.creator.clone())?
+        );
+        reward.count = 0;
+    }
+    msgs.extend(util::save_series(deps.storage, &series, None, now)?);
+
+    Ok(
+        Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "close_series")
+            .add_attribute("series_id", series_id)
+    )
+}
+
+pub fn execute_set_allowlist(
+    deps: DepsMut,
+    info: MessageInfo,
+    box_id: String,
+    root: Option<HexBinary>
+) -> Result<Response, ContractError> {
+    let mut fortune_box = match BOX_MAP.load(deps.storage, box_id.clone()) {
+        Ok(fortune_box) => fortune_box,
+        Err(_) => {
+            return Err(ContractError::BoxNotFound {});
+        }
+    };
+    if fortune_box.creator != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if fortune_box.status != BoxStatus::Draft {
+        return Err(ContractError::BoxLocked {});
+    }
+    util::check_merkle_root(&root)?;
+
+    fortune_box.allowlist_root = root.clone();
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+    Ok(
+        Response::new()
+            .add_attribute("action", "set_allowlist")
+            .add_attribute("box_id", box_id)
+            .add_attribute("root", root.map_or("none".to_string(), |x| x.to_hex()))
+    )
+}
+
+pub fn execute_issue_credits(
+    deps: DepsMut,
+    info: MessageInfo,
+    scope: CreditScope,
+    recipients: Vec<CreditRecipient>,
+    expires_at: Option<u64>
+) -> Result<Response, ContractError> {
+    let scope = util::check_credit_issuer(deps.storage, deps.api, scope, info.sender)?;
+
+    let mut total = 0u64;
+    for recipient in recipients.iter() {
+        let address = util::validate_addr(deps.api, "recipient", &recipient.address)?;
+        util::add_credits(deps.storage, address, scope.clone(), recipient.count, expires_at)?;
+        total = total.checked_add(recipient.count).ok_or(ContractError::InvalidCreditCount {})?;
+    }
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "issue_credits")
+            .add_attribute("scope", util::credit_scope_key(&scope))
+            .add_attribute("recipients", recipients.len().to_string())
+            .add_attribute("credits", total.to_string())
+    )
+}
+
+pub fn execute_create_credit_drop(
+    deps: DepsMut,
+    info: MessageInfo,
+    scope: CreditScope,
+    root: HexBinary,
+    expires_at: Option<u64>
+) -> Result<Response, ContractError> {
+    let scope = util::check_credit_issuer(deps.storage, deps.api, scope, info.sender.clone())?;
+    util::check_merkle_root(&Some(root.clone()))?;
+
+    let id = CREDIT_DROP_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    let drop = CreditDrop { id, issuer: info.sender, scope, root, expires_at };
+    CREDIT_DROP_COUNT.save(deps.storage, &id)?;
+    CREDIT_DROPS.save(deps.storage, id, &drop)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "create_credit_drop")
+            .add_attribute("drop_id", id.to_string())
+            .add_attribute("scope", util::credit_scope_key(&drop.scope))
+    )
+}
+
+pub fn execute_claim_credits(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    drop_id: u64,
+    count: u64,
+    proof: Vec<HexBinary>
+) -> Result<Response, ContractError> {
+    let drop = match CREDIT_DROPS.may_load(deps.storage, drop_id)? {
+        Some(drop) => drop,
+        None => {
+            return Err(ContractError::DropNotFound {});
+        }
+    };
+    if drop.expires_at.is_some_and(|expires_at| env.block.time.seconds() >= expires_at) {
+        return Err(ContractError::DropExpired {});
+    }
+    if CREDIT_CLAIMS.has(deps.storage, (drop_id, info.sender.clone())) {
+        return Err(ContractError::AlreadyClaimed {});
+    }
+    if count == 0 {
+        return Err(ContractError::InvalidCreditCount {});
+    }
+    let leaf = merkle::leaf_hash(info.sender.as_str(), Some(count));
+    if !util::verify_proof(&drop.root, leaf, &proof)? {
+        return Err(ContractError::InvalidProof {});
+    }
+
+    CREDIT_CLAIMS.save(deps.storage, (drop_id, info.sender.clone()), &count)?;
+    util::add_credits(deps.storage, info.sender.clone(), drop.scope, count, drop.expires_at)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "claim_credits")
+            .add_attribute("drop_id", drop_id.to_string())
+            .add_attribute("address", info.sender)
+            .add_attribute("credits", count.to_string())
+    )
+}
+
+pub fn execute_open_box(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    box_id: String,
+    proof: Option<AllowlistProof>,
+    referrer: Option<String>
+) -> Result<Response, ContractError> {
+    let referrer = match referrer {
+        Some(referrer) => Some(util::validate_addr(deps.api, "referrer", &referrer)?),
+        None => None,
+    };
+    // Openers holding a credit may call without funds
+    let payment = if info.funds.is_empty() {
+        OpenPayment::Nothing
+    } else {
+        match one_coin(&info) {
+            Ok(payment) => OpenPayment::Funds(payment.into()),
+            Err(_) => {
+                return Err(ContractError::PaymentFailed {});
+            }
+        }
+    };
+    open_box(deps, env, info.sender, box_id, payment, proof, referrer)
+}
+
+/// What an opener handed over for an open, the box price, one of its keys, a credit or the
+/// NFTs of a trade-in.
+enum OpenPayment {
+    Nothing,
+    Funds(Asset),
+    Key(KeyUsed),
+    Credit(CreditScope),
+    TradeIn(Vec<TradedNft>),
+}
+
+/// Opens a box for `opener` once `payment` matches the box price or key and the opener is
+/// allowlisted. A `referrer` earns part of the fee of paid opens.
+fn open_box(
+    deps: DepsMut,
+    env: Env,
+    opener: Addr,
+    box_id: String,
+    mut payment: OpenPayment,
+    proof: Option<AllowlistProof>,
+    referrer: Option<Addr>
+) -> Result<Response, ContractError> {
+    if referrer.as_ref() == Some(&opener) {
+        return Err(ContractError::SelfReferral {});
+    }
+    let fortune_box = BOX_MAP.load(deps.storage, box_id.clone());
+    match fortune_box {
+        Ok(mut fortune_box) => {
+            // Credits are spent before any payment, which then goes back to the opener
+            let now = env.block.time.seconds();
+            let mut refund = None;
+            if !matches!(payment, OpenPayment::Key(_) | OpenPayment::TradeIn(_)) {
+                if let Some(scope) = util::consume_credit(deps.storage, &fortune_box, &opener, now)? {
+                    if let OpenPayment::Funds(funds) = payment {
+                        refund = Some(funds);
+                    }
+                    payment = OpenPayment::Credit(scope);
+                }
+            }
+
+            match (&fortune_box.trade_in, &payment) {
+                (Some(_), OpenPayment::Funds(_) | OpenPayment::Nothing) => {
+                    return Err(ContractError::TradeInRequired {});
+                }
+                (None, OpenPayment::TradeIn(_)) => {
+                    return Err(ContractError::InvalidTradeIn {});
+                }
+                _ => {}
+            }
+            match (&fortune_box.key, &payment) {
+                (None, OpenPayment::Funds(payment)) => {
+                    if payment.info != fortune_box.price.info {
+                        return Err(ContractError::PaymentFailed {});
+                    }
+                    if payment.amount != fortune_box.price.amount {
+                        return Err(ContractError::AmountNotMatch {});
+                    }
+                }
+                (None, OpenPayment::Nothing) => {
+                    return Err(ContractError::PaymentFailed {});
+                }
+                (Some(_), OpenPayment::Funds(_) | OpenPayment::Nothing) => {
+                    return Err(ContractError::KeyRequired {});
+                }
+                (_, OpenPayment::Key(_) | OpenPayment::Credit(_) | OpenPayment::TradeIn(_)) => {}
+            }
+
+            match util::box_status(&fortune_box, env.block.time.seconds()) {
+                BoxStatus::Live => {
+                    fortune_box.status = BoxStatus::Live;
+                    fortune_box.resume_at = None;
+                }
+                BoxStatus::Paused => {
+                    return Err(ContractError::BoxPaused {});
+                }
+                BoxStatus::SoldOut | BoxStatus::Cancelled | BoxStatus::Expired => {
+                    return Err(ContractError::BoxTerminated {});
+                }
+                status => {
+                    return Err(ContractError::BoxNotLive { status: format!("{:?}", status) });
+                }
+            }
+
+            let limit = util::check_allowlist(&fortune_box, &opener, proof)?;
+            util::check_holder_gate(deps.querier, &fortune_box, &opener)?;
+            util::record_open(deps.storage, &fortune_box, &opener, limit, env.block.time.seconds())?;
+            // A wrong key is rejected before the draw has paid anything out
+            let mut key_msgs = match &payment {
+                OpenPayment::Key(key) => util::use_key(deps.storage, &mut fortune_box, key)?,
+                _ => vec![],
+            };
+            if let BoxKind::Raffle { .. } = fortune_box.kind {
+                return buy_ticket(deps, env, fortune_box, opener, payment, refund);
+            }
+
+            let mut hasher = Sha256::new();
+            hasher.update(env.block.time.seconds().to_string());
+            let result = hasher.finalize();
+
+            // Manually convert the first 8 bytes of the hash into a u64
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&result[0..8]);
+            let random_number = u64::from_be_bytes(bytes);
+
+            let outcome = match util::draw_outcome(&fortune_box, &random_number.to_be_bytes()) {
+                Some(outcome) => outcome,
+                None => {
+                    return Err(ContractError::BoxTerminated {});
+                }
+            };
+
+            let mut msgs = Vec::new();
+            // Opening again accepts a result still waiting on a reroll
+            if let Some(pending) = PENDING_REWARDS.may_load(deps.storage, (box_id.clone(), opener.clone()))? {
+                msgs.extend(util::finalize_pending(deps.storage, pending)?);
+            }
+
+            let mut points = Uint128::zero();
+            let mut pending = None;
+            match outcome {
+                DrawOutcome::Reward(reward_id) => {
+                    //find the reward with id == winner_id
+                    let reward = match fortune_box.rewards.iter_mut().find(|x| x.id == reward_id) {
+                        Some(reward) => reward,
+                        None => {
+                            return Err(ContractError::RewardNotFound {});
+                        }
+                    };
+
+                    match &fortune_box.reroll {
+                        // Held back while the opener may still reroll it
+                        Some(reroll) => {
+                            pending = Some(PendingReward {
+                                box_id: box_id.clone(),
+                                opener: opener.clone(),
+                                open_index: fortune_box.opened,
+                                reward: RewardData { count: 1, ..reward.clone() },
+                                expires_at: env.block.time
+                                    .seconds()
+                                    .checked_add(reroll.window)
+                                    .ok_or(ContractError::InvalidReroll {})?,
+                                rerolls: 0,
+                            });
+                        }
+                        None =>
+                            msgs.extend(
+                                util::reward_transfer_messages(deps.storage, reward, 1, opener.clone())?
+                            ),
+                    }
+
+                    // Directly modify the 'count' of the reward
+                    reward.count -= 1;
+                }
+                DrawOutcome::Empty => {
+                    points = fortune_box.consolation_points;
+                }
+            }
+
+            // Only opens paid in the native token count towards `inj_spent`
+            let cfg = CONFIG.load(deps.storage)?;
+            let mut native_spent = Uint128::zero();
+            let mut jackpot = None;
+            match &payment {
+                OpenPayment::Funds(payment) => {
+                    let fees: Uint128 =
+                        (payment.amount * Uint128::from(5u64)) / Uint128::from(100u64);
+                    let jackpot_share = payment.amount.multiply_ratio(cfg.jackpot_bps, util::MAX_BPS);
+                    let creator_cut = payment.amount - fees - jackpot_share;
+                    if !creator_cut.is_zero() {
+                        msgs.push(
+                            util::transfer_token_message(
+                                Asset { info: payment.info.clone(), amount: creator_cut },
+                                fortune_box.creator.clone()
+                            )?
+                        );
+                    }
+                    util::feed_jackpot(
+                        deps.storage,
+                        Asset { info: payment.info.clone(), amount: jackpot_share }
+                    )?;
+
+                    let referral = match &referrer {
+                        Some(referrer) =>
+                            util::pay_referral(
+                                deps.storage,
+                                referrer.clone(),
+                                &(Asset { info: payment.info.clone(), amount: fees })
+                            )?,
+                        None => Uint128::zero(),
+                    };
+                    util::accrue_fee(deps.storage, payment.info.key(), fees - referral)?;
+                    // Rolled once this open's fee is booked, so it can go into the reseed
+                    if util::jackpot_hit(&result, cfg.jackpot_odds) {
+                        jackpot = util::win_jackpot(
+                            deps.storage,
+                            &payment.info,
+                            opener.clone(),
+                            box_id.clone(),
+                            env.block.time.seconds()
+                        )?;
+                        if let Some(win) = &jackpot {
+                            msgs.push(util::transfer_token_message(win.prize.clone(), opener.clone())?);
+                        }
+                    }
+                    if payment.info == (AssetInfo::Native { denom: cfg.native_token }) {
+                        native_spent = payment.amount;
+                    }
+                }
+                OpenPayment::Key(_) => {
+                    msgs.append(&mut key_msgs);
+                }
+                OpenPayment::TradeIn(nfts) => {
+                    msgs.extend(util::use_trade_in(&fortune_box, nfts)?);
+                }
+                OpenPayment::Credit(_) | OpenPayment::Nothing => {}
+            }
+            if let Some(refund) = refund {
+                msgs.push(util::transfer_token_message(refund, opener.clone())?);
+            }
+
+            let (paid, key, credit, traded_in) = match payment {
+                OpenPayment::Funds(payment) => (Some(payment), None, None, vec![]),
+                OpenPayment::Key(key) => (None, Some(key), None, vec![]),
+                OpenPayment::Credit(scope) => (None, None, Some(scope), vec![]),
+                OpenPayment::TradeIn(nfts) => (None, None, None, nfts),
+                OpenPayment::Nothing => (None, None, None, vec![]),
+            };
+            let record = OpenRecord {
+                index: fortune_box.opened,
+                opener: opener.clone(),
+                time: env.block.time.seconds(),
+                reward_id: match outcome {
+                    DrawOutcome::Reward(reward_id) => Some(reward_id),
+                    DrawOutcome::Empty => None,
+                },
+                payment: paid,
+                key,
+                credit,
+                traded_in,
+            };
+            OPEN_HISTORY.save(deps.storage, (box_id.clone(), record.index), &record)?;
+            fortune_box.opened += 1;
+            if let Some(pending) = &pending {
+                PENDING_REWARDS.save(deps.storage, (box_id.clone(), opener.clone()), pending)?;
+            }
+
+            util::record_user_open(
+                deps.storage,
+                opener.clone(),
+                &box_id,
+                if pending.is_some() { None } else { record.reward_id },
+                native_spent,
+                points
+            )?;
+
+            // Close the box once nothing drawable is left or the open cap is hit, returning leftovers
+            let capped = fortune_box.open_limits.total.is_some_and(|x| fortune_box.opened >= x);
+            if capped || util::draw_outcome(&fortune_box, &[]).is_none() {
+                fortune_box.status = BoxStatus::SoldOut;
+                let shared = fortune_box.series.is_some();
+                for reward in fortune_box.rewards.iter_mut().filter(|_| !shared) {
+                    msgs.extend(
+                        util::reward_transfer_messages(
+                            deps.storage,
+                            reward,
+                            reward.count,
+                            fortune_box.creator.clone()
+                        )?
+                    );
+                    reward.count = 0;
+                }
+                msgs.extend(util::release_keys(deps.storage, &mut fortune_box)?);
+                util::unfeature(deps.storage, &mut fortune_box);
+            }
+
+            msgs.extend(util::sync_series(deps.storage, &fortune_box, now)?);
+            BOX_MAP.save(deps.storage, box_id, &fortune_box)?;
+            let reward_id = match outcome {
+                DrawOutcome::Reward(reward_id) => reward_id.to_string(),
+                DrawOutcome::Empty => "none".to_string(),
+            };
+            Ok(
+                Response::new()
+                    .add_messages(msgs)
+                    .add_attribute("action", "execute_open_box")
+                    .add_attribute("seed", random_number.to_string())
+                    .add_attribute("reward_id", reward_id)
+                    .add_attribute("points", points)
+                    .add_attribute(
+                        "jackpot",
+                        jackpot.map_or(Uint128::zero(), |win| win.prize.amount)
+                    )
+            )
+        }
+        Err(_) => { Err(ContractError::BoxNotFound {}) }
+    }
+}
+
+/// Sells a raffle ticket, the payment stays in the box until the draw.
+fn buy_ticket(
+    deps: DepsMut,
+    env: Env,
+    mut fortune_box: FortuneBox,
+    opener: Addr,
+    payment: OpenPayment,
+    refund: Option<Asset>
+) -> Result<Response, ContractError> {
+    if let BoxKind::Raffle { max_tickets, .. } = fortune_box.kind {
+        if fortune_box.opened >= max_tickets.unwrap_or(util::MAX_RAFFLE_TICKETS) {
+            return Err(ContractError::RaffleFull {});
+        }
+    }
+    let cfg = CONFIG.load(deps.storage)?;
+    let mut msgs = Vec::new();
+    if let Some(refund) = refund {
+        msgs.push(util::transfer_token_message(refund, opener.clone())?);
+    }
+
+    let (paid, credit) = match payment {
+        OpenPayment::Funds(payment) => (Some(payment), None),
+        OpenPayment::Credit(scope) => (None, Some(scope)),
+        OpenPayment::Key(_) | OpenPayment::TradeIn(_) | OpenPayment::Nothing => {
+            return Err(ContractError::PaymentFailed {});
+        }
+    };
+    let mut native_spent = Uint128::zero();
+    if let Some(paid) = &paid {
+        fortune_box.raffle_pot += paid.amount;
+        util::escrow(deps.storage, paid)?;
+        if paid.info == (AssetInfo::Native { denom: cfg.native_token }) {
+            native_spent = paid.amount;
+        }
+    }
+
+    let record = OpenRecord {
+        index: fortune_box.opened,
+        opener: opener.clone(),
+        time: env.block.time.seconds(),
+        reward_id: None,
+        payment: paid,
+        key: None,
+        credit,
+        traded_in: vec![],
+    };
+    OPEN_HISTORY.save(deps.storage, (fortune_box.id.clone(), record.index), &record)?;
+    fortune_box.opened += 1;
+    util::record_user_open(
+        deps.storage,
+        opener.clone(),
+        &fortune_box.id,
+        None,
+        native_spent,
+        Uint128::zero()
+    )?;
+
+    BOX_MAP.save(deps.storage, fortune_box.id.clone(), &fortune_box)?;
+    Ok(
+        Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "buy_ticket")
+            .add_attribute("box_id", fortune_box.id)
+            .add_attribute("buyer", opener)
+            .add_attribute("ticket", record.index.to_string())
+    )
+}
+
+/// Puts the pending reward of `opener` back into the box and draws again for `payment`, which
+/// is split like an open price.
+pub fn execute_reroll(
+    deps: DepsMut,
+    env: Env,
+    opener: Addr,
+    box_id: String,
+    payment: Asset
+) -> Result<Response, ContractError> {
+    let mut fortune_box = match BOX_MAP.may_load(deps.storage, box_id.clone())? {
+        Some(fortune_box) => fortune_box,
+        None => {
+            return Err(ContractError::BoxNotFound {});
+        }
+    };
+    let reroll = match &fortune_box.reroll {
+        Some(reroll) => reroll.clone(),
+        None => {
+            return Err(ContractError::RerollNotEnabled {});
+        }
+    };
+    let key = (box_id.clone(), opener.clone());
+    let mut pending = match PENDING_REWARDS.may_load(deps.storage, key.clone())? {
+        Some(pending) => pending,
+        None => {
+            return Err(ContractError::NoPendingReward {});
+        }
+    };
+    let now = env.block.time.seconds();
+    if now >= pending.expires_at {
+        return Err(ContractError::RerollExpired {});
+    }
+    if payment.info != fortune_box.price.info {
+        return Err(ContractError::PaymentFailed {});
+    }
+    if payment.amount != reroll.fee {
+        return Err(ContractError::AmountNotMatch {});
+    }
+    let status = util::box_status(&fortune_box, now);
+    if status != BoxStatus::Live {
+        return Err(ContractError::BoxNotLive { status: format!("{:?}", status) });
+    }
+
+    match fortune_box.rewards.iter_mut().find(|x| x.id == pending.reward.id) {
+        Some(reward) => {
+            reward.count += 1;
+        }
+        None => {
+            return Err(ContractError::RewardNotFound {});
+        }
+    }
+
+    // Rerolls in the same block must not repeat the open's draw
+    pending.rerolls += 1;
+    let mut hasher = Sha256::new();
+    hasher.update(now.to_string());
+    hasher.update(opener.as_bytes());
+    hasher.update(pending.open_index.to_be_bytes());
+    hasher.update(pending.rerolls.to_be_bytes());
+    let result = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&result[0..8]);
+    let random_number = u64::from_be_bytes(bytes);
+
+    let outcome = match util::draw_outcome(&fortune_box, &random_number.to_be_bytes()) {
+        Some(outcome) => outcome,
+        None => {
+            return Err(ContractError::BoxTerminated {});
+        }
+    };
+    let reward_id = match outcome {
+        DrawOutcome::Reward(reward_id) => {
+            let reward = match fortune_box.rewards.iter_mut().find(|x| x.id == reward_id) {
+                Some(reward) => reward,
+                None => {
+                    return Err(ContractError::RewardNotFound {});
+                }
+            };
+            reward.count -= 1;
+            pending.reward = RewardData { count: 1, ..reward.clone() };
+            pending.expires_at = now.checked_add(reroll.window).ok_or(ContractError::InvalidReroll {})?;
+            PENDING_REWARDS.save(deps.storage, key, &pending)?;
+            Some(reward_id)
+        }
+        DrawOutcome::Empty => {
+            PENDING_REWARDS.remove(deps.storage, key);
+            util::record_user_reward(
+                deps.storage,
+                opener.clone(),
+                &box_id,
+                None,
+                fortune_box.consolation_points
+            )?;
+            None
+        }
+    };
+    let mut record = OPEN_HISTORY.load(deps.storage, (box_id.clone(), pending.open_index))?;
+    record.reward_id = reward_id;
+    OPEN_HISTORY.save(deps.storage, (box_id.clone(), pending.open_index), &record)?;
+
+    let fees: Uint128 = (payment.amount * Uint128::from(5u64)) / Uint128::from(100u64);
+    util::accrue_fee(deps.storage, payment.info.key(), fees)?;
+    let msg = util::transfer_token_message(
+        Asset { info: payment.info, amount: payment.amount - fees },
+        fortune_box.creator.clone()
+    )?;
+
+    let msgs = util::sync_series(deps.storage, &fortune_box, now)?;
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+    Ok(
+        Response::new()
+            .add_message(msg)
+            .add_messages(msgs)
+            .add_attribute("action", "reroll")
+            .add_attribute("box_id", box_id)
+            .add_attribute("opener", opener)
+            .add_attribute("reward_id", reward_id.map_or("none".to_string(), |x| x.to_string()))
+            .add_attribute("rerolls", pending.rerolls.to_string())
+    )
+}
+
+pub fn execute_accept(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    box_id: String,
+    address: Option<String>
+) -> Result<Response, ContractError> {
+    let opener = match address {
+        Some(address) => util::validate_addr(deps.api, "address", &address)?,
+        None => info.sender.clone(),
+    };
+    let pending = match PENDING_REWARDS.may_load(deps.storage, (box_id.clone(), opener.clone()))? {
+        Some(pending) => pending,
+        None => {
+            return Err(ContractError::NoPendingReward {});
+        }
+    };
+    if opener != info.sender && env.block.time.seconds() < pending.expires_at {
+        return Err(ContractError::RerollWindowOpen {});
+    }
+
+    let reward_id = pending.reward.id;
+    Ok(
+        Response::new()
+            .add_messages(util::finalize_pending(deps.storage, pending)?)
+            .add_attribute("action", "accept_reward")
+            .add_attribute("box_id", box_id)
+            .add_attribute("opener", opener)
+            .add_attribute("reward_id", reward_id.to_string())
+    )
+}
+
+/// Draws every reward of an ended raffle over the sold tickets, each ticket wins at most once.
+/// Rewards left over and the ticket sales minus the fee go to the creator.
+pub fn execute_draw_raffle(
+    deps: DepsMut,
+    env: Env,
+    box_id: String
+) -> Result<Response, ContractError> {
+    let mut fortune_box = match BOX_MAP.may_load(deps.storage, box_id.clone())? {
+        Some(fortune_box) => fortune_box,
+        None => {
+            return Err(ContractError::BoxNotFound {});
+        }
+    };
+    let min_tickets = match fortune_box.kind {
+        BoxKind::Raffle { min_tickets, .. } => min_tickets.unwrap_or(1),
+        BoxKind::Instant => {
+            return Err(ContractError::NotARaffle {});
+        }
+    };
+    match util::box_status(&fortune_box, env.block.time.seconds()) {
+        BoxStatus::Expired => {}
+        BoxStatus::SoldOut | BoxStatus::Cancelled => {
+            return Err(ContractError::BoxTerminated {});
+        }
+        _ => {
+            return Err(ContractError::RaffleNotEnded {});
+        }
+    }
+
+    let mut msgs = Vec::new();
+    let tickets = fortune_box.opened;
+    if tickets < min_tickets {
+        msgs.extend(util::refund_tickets(deps.storage, &mut fortune_box)?);
+        fortune_box.status = BoxStatus::Cancelled;
+    } else {
+        let seed = util::raffle_seed(deps.storage, &fortune_box)?;
+
+        let mut remaining: Vec<u64> = (0..tickets).collect();
+        let mut winners: Vec<WinnerStruct> = vec![];
+        let mut round = 0u64;
+        for reward in fortune_box.rewards.iter_mut() {
+            while reward.count > 0 && !remaining.is_empty() {
+                let pick = util::sample_index(&seed, round, remaining.len() as u64);
+                let ticket = remaining.swap_remove(pick as usize);
+                round += 1;
+
+                let mut record = OPEN_HISTORY.load(deps.storage, (box_id.clone(), ticket))?;
+                record.reward_id = Some(reward.id);
+                OPEN_HISTORY.save(deps.storage, (box_id.clone(), ticket), &record)?;
+                msgs.extend(
+                    util::reward_transfer_messages(deps.storage, reward, 1, record.opener.clone())?
+                );
+                util::record_user_reward(
+                    deps.storage,
+                    record.opener.clone(),
+                    &box_id,
+                    Some(reward.id),
+                    Uint128::zero()
+                )?;
+
+                let won = RewardData { count: 1, ..reward.clone() };
+                match winners.iter_mut().find(|x| x.address == record.opener) {
+                    Some(winner) => winner.rewards.push(won),
+                    None =>
+                        winners.push(WinnerStruct {
+                            address: record.opener,
+                            rewards: vec![won],
+                        }),
+                }
+                reward.count -= 1;
+            }
+        }
+        fortune_box.winners = Some(winners);
+
+        // Ticket sales are paid out like opens, 5% fee and the rest to the creator
+        let pot = fortune_box.raffle_pot;
+        let fees = (pot * Uint128::from(5u64)) / Uint128::from(100u64);
+        if !pot.is_zero() {
+            util::release_escrow(
+                deps.storage,
+                &(Asset { info: fortune_box.price.info.clone(), amount: pot })
+            )?;
+            msgs.push(
+                util::transfer_token_message(
+                    Asset { info: fortune_box.price.info.clone(), amount: pot - fees },
+                    fortune_box.creator.clone()
+                )?
+            );
+            util::accrue_fee(deps.storage, fortune_box.price.info.key(), fees)?;
+        }
+        fortune_box.raffle_pot = Uint128::zero();
+        fortune_box.status = BoxStatus::SoldOut;
+        util::unfeature(deps.storage, &mut fortune_box);
+    }
+
+    for reward in fortune_box.rewards.iter_mut() {
+        msgs.extend(
+            util::reward_transfer_messages(
+                deps.storage,
+                reward,
+                reward.count,
+                fortune_box.creator.clone()
+            )?
+        );
+        reward.count = 0;
+    }
+
+    BOX_MAP.save(deps.storage, box_id.clone(), &fortune_box)?;
+    Ok(
+        Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "draw_raffle")
+            .add_attribute("box_id", box_id)
+            .add_attribute("tickets", tickets.to_string())
+            .add_attribute("status", format!("{:?}", fortune_box.status))
+    )
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetConfig {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::GetBoxes { status } => to_json_binary(&query_boxes(deps, env, status)?),
+        QueryMsg::GetUsers {} => to_json_binary(&query_users(deps)?),
+        QueryMsg::GetBoxOdds { box_id } => to_json_binary(&query_box_odds(deps, box_id)?),
+        QueryMsg::GetExpectedValue { box_id } =>
+            to_json_binary(&query_expected_value(deps, box_id)?),
+        QueryMsg::GetOpenAllowance { box_id, address } =>
+            to_json_binary(&query_open_allowance(deps, env, box_id, address)?),
+        QueryMsg::GetOpenHistory { box_id, start_after, limit } =>
+            to_json_binary(&query_open_history(deps, box_id, start_after, limit)?),
+        QueryMsg::GetCredits { address } => to_json_binary(&query_credits(deps, env, address)?),
+        QueryMsg::GetReferralStats { address } =>
+            to_json_binary(&query_referral_stats(deps, address)?),
+        QueryMsg::GetReferralLeaderboard { asset, limit } =>
+            to_json_binary(&query_referral_leaderboard(deps, asset, limit)?),
+        QueryMsg::GetSeries { series_id } => to_json_binary(&query_series(deps, series_id)?),
+        QueryMsg::GetPendingBundles { box_id } =>
+            to_json_binary(&query_pending_bundles(deps, box_id)?),
+        QueryMsg::GetPendingReward { box_id, address } =>
+            to_json_binary(&query_pending_reward(deps, box_id, address)?),
+        QueryMsg::GetTradeInDeposits { box_id, address } =>
+            to_json_binary(&query_trade_in_deposits(deps, box_id, address)?),
+        QueryMsg::GetRaffleTickets { box_id, address } =>
+            to_json_binary(&query_raffle_tickets(deps, box_id, address)?),
+        QueryMsg::GetJackpots {} => to_json_binary(&query_jackpots(deps)?),
+        QueryMsg::GetJackpotHistory { start_before, limit } =>
+            to_json_binary(&query_jackpot_history(deps, start_before, limit)?),
+        QueryMsg::ListFeatured {} => to_json_binary(&query_featured(deps, env)?),
+        QueryMsg::GetTreasury {} => to_json_binary(&query_treasury(deps)?),
+        QueryMsg::GetAllowedAssets {} => to_json_binary(&query_allowed_assets(deps)?),
+        QueryMsg::ListAuctions { open_only } =>
+            to_json_binary(&query_auctions(deps, env, open_only.unwrap_or(false))?),
+        QueryMsg::GetAuctionBids { auction_id } =>
+            to_json_binary(&query_auction_bids(deps, auction_id)?),
+    }
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        owner: config.owner.clone(),
+        token: config.native_token,
+        feature_fees: config.feature_fees,
+        feature_period: config.feature_period,
+        max_featured: config.max_featured,
+        injscribed_address: config.injscribed_address,
+        max_odds: config.max_odds,
+        min_box_odds: config.min_box_odds,
+        max_box_odds: config.max_box_odds,
+        referral_bps: config.referral_bps,
+        jackpot_bps: config.jackpot_bps,
+        jackpot_odds: config.jackpot_odds,
+    })
+}
+
+pub fn query_boxes(
+    deps: Deps,
+    env: Env,
+    status: Option<BoxStatus>
+) -> StdResult<BoxesResponse> {
+    let now = env.block.time.seconds();
+    let boxes: StdResult<Vec<FortuneBox>> = BOX_MAP.range(
         deps.storage,
         None,
         None,
         Order::Ascending
     )
-        .map(|item| item.map(|(_, v)| v))
+        .map(|item|
+            item.map(|(_, mut v)| {
+                v.status = util::box_status(&v, now);
+                v
+            })
+        )
+        .filter(|item| {
+            match (item, &status) {
+                (Ok(v), Some(status)) => v.status == *status,
+                _ => true,
+            }
+        })
         .collect();
 
     match boxes {
@@ -656,3 +2527,308 @@ pub fn query_users(deps: Deps) -> StdResult<UsersInfoResponse> {
         Err(_) => Ok(UsersInfoResponse { users: Vec::new() }),
     }
 }
+
+pub fn query_box_odds(deps: Deps, box_id: String) -> StdResult<BoxOddsResponse> {
+    let fortune_box = BOX_MAP.load(deps.storage, box_id.clone())?;
+    let (probabilities, empty_probability) = util::outcome_probabilities(&fortune_box);
+
+    let rewards = fortune_box.rewards
+        .iter()
+        .zip(probabilities)
+        .map(|(reward, (_, probability))| RewardOdds {
+            reward_id: reward.id,
+            odds: reward.odds,
+            count: reward.count,
+            share: Decimal::from_ratio(reward.odds, fortune_box.max_odds),
+            probability,
+        })
+        .collect();
+
+    Ok(BoxOddsResponse {
+        box_id,
+        odds_model: fortune_box.odds_model,
+        max_odds: fortune_box.max_odds,
+        rewards,
+        empty_probability,
+    })
+}
+
+pub fn query_expected_value(deps: Deps, box_id: String) -> StdResult<ExpectedValueResponse> {
+    let fortune_box = BOX_MAP.load(deps.storage, box_id.clone())?;
+    let (weights, empty) = util::outcome_weights(&fortune_box);
+    let total: u128 = weights.iter().map(|(_, weight)| weight).sum::<u128>() + empty;
+
+    // Sum amount * weight per denom and divide once at the end to stay exact
+    let mut token_sums: Vec<(String, Uint256)> = Vec::new();
+    let mut nft_weight: u128 = 0;
+    for (reward, (_, weight)) in fortune_box.rewards.iter().zip(weights.iter()) {
+        let has_nft =
+            reward.reward_type == RewardType::Nft ||
+            reward.bundle.iter().any(|item| matches!(item, BundleItem::Nft { .. }));
+        if has_nft {
+            nft_weight += weight;
+        }
+        for asset in util::reward_assets(reward) {
+            let denom = asset.info.key();
+            let value = Uint256::from(asset.amount) * Uint256::from(*weight);
+            match token_sums.iter_mut().find(|(d, _)| *d == denom) {
+                Some((_, sum)) => {
+                    *sum += value;
+                }
+                None => token_sums.push((denom, value)),
+            }
+        }
+    }
+
+    let ratio = |numerator: Uint256| -> Decimal256 {
+        if total == 0 {
+            Decimal256::zero()
+        } else {
+            Decimal256::from_ratio(numerator, Uint256::from(total))
+        }
+    };
+    let share = |numerator: u128| -> Decimal {
+        if total == 0 { Decimal::zero() } else { Decimal::from_ratio(numerator, total) }
+    };
+
+    Ok(ExpectedValueResponse {
+        box_id,
+        price: fortune_box.price,
+        win_probability: share(total - empty),
+        nft_probability: share(nft_weight),
+        tokens: token_sums
+            .into_iter()
+            .map(|(denom, sum)| DenomValue { denom, amount: ratio(sum) })
+            .collect(),
+        points: ratio(Uint256::from(fortune_box.consolation_points) * Uint256::from(empty)),
+    })
+}
+
+pub fn query_open_allowance(
+    deps: Deps,
+    env: Env,
+    box_id: String,
+    address: String
+) -> StdResult<OpenAllowanceResponse> {
+    let fortune_box = BOX_MAP.load(deps.storage, box_id)?;
+    let address = deps.api.addr_validate(&address)?;
+    util::open_allowance(deps.storage, &fortune_box, address, env.block.time.seconds())
+}
+
+pub fn query_open_history(
+    deps: Deps,
+    box_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>
+) -> StdResult<OpenHistoryResponse> {
+    let limit = limit.unwrap_or(30).min(100) as usize;
+    let opens = OPEN_HISTORY.prefix(box_id)
+        .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, record)| record))
+        .collect::<StdResult<_>>()?;
+
+    Ok(OpenHistoryResponse { opens })
+}
+
+pub fn query_credits(deps: Deps, env: Env, address: String) -> StdResult<CreditsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let now = env.block.time.seconds();
+    let credits = CREDITS.prefix(address.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, grant)| grant))
+        .filter(|grant| {
+            grant.as_ref().map_or(true, |grant| grant.expires_at.is_none_or(|x| now < x))
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(CreditsResponse { address, credits })
+}
+
+pub fn query_referral_stats(deps: Deps, address: String) -> StdResult<ReferralStatsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let stats = REFERRALS.may_load(deps.storage, address.clone())?.unwrap_or(ReferralStats {
+        address: address.clone(),
+        referrals: 0,
+        earned: vec![],
+    });
+    let claimable = REFERRAL_BALANCES.prefix(address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, balance)| balance))
+        .collect::<StdResult<_>>()?;
+
+    Ok(ReferralStatsResponse { stats, claimable })
+}
+
+pub fn query_referral_leaderboard(
+    deps: Deps,
+    asset: Option<AssetInfo>,
+    limit: Option<u32>
+) -> StdResult<ReferralLeaderboardResponse> {
+    let limit = limit.unwrap_or(10).min(100) as usize;
+    let earned = |stats: &ReferralStats, asset: &AssetInfo| {
+        stats.earned
+            .iter()
+            .find(|x| x.info == *asset)
+            .map_or(Uint128::zero(), |x| x.amount)
+    };
+    let mut referrers: Vec<ReferralStats> = REFERRALS.range(
+        deps.storage,
+        None,
+        None,
+        Order::Ascending
+    )
+        .map(|item| item.map(|(_, stats)| stats))
+        .collect::<StdResult<_>>()?;
+    match &asset {
+        Some(asset) => {
+            referrers.retain(|stats| !earned(stats, asset).is_zero());
+            referrers.sort_by_key(|stats| Reverse(earned(stats, asset)));
+        }
+        None => referrers.sort_by_key(|stats| Reverse(stats.referrals)),
+    }
+    referrers.truncate(limit);
+
+    Ok(ReferralLeaderboardResponse { referrers })
+}
+
+pub fn query_pending_reward(
+    deps: Deps,
+    box_id: String,
+    address: String
+) -> StdResult<PendingRewardResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let pending = PENDING_REWARDS.may_load(deps.storage, (box_id, address))?;
+
+    Ok(PendingRewardResponse { pending })
+}
+
+pub fn query_series(deps: Deps, series_id: String) -> StdResult<SeriesResponse> {
+    let series = SERIES.load(deps.storage, series_id)?;
+    Ok(SeriesResponse { series })
+}
+
+pub fn query_pending_bundles(deps: Deps, box_id: String) -> StdResult<PendingBundlesResponse> {
+    let bundles = PENDING_BUNDLES.prefix(box_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, bundle)| bundle))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PendingBundlesResponse { bundles })
+}
+
+pub fn query_trade_in_deposits(
+    deps: Deps,
+    box_id: String,
+    address: String
+) -> StdResult<TradeInDepositsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let fortune_box = BOX_MAP.load(deps.storage, box_id.clone())?;
+    let deposits = TRADE_IN_DEPOSITS.may_load(deps.storage, (box_id, address))?.unwrap_or_default();
+
+    Ok(TradeInDepositsResponse {
+        deposits,
+        required: fortune_box.trade_in.map_or(0, |trade_in| trade_in.count),
+    })
+}
+
+pub fn query_raffle_tickets(
+    deps: Deps,
+    box_id: String,
+    address: String
+) -> StdResult<RaffleTicketsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let fortune_box = BOX_MAP.load(deps.storage, box_id.clone())?;
+    let tickets = BOX_OPENS.may_load(deps.storage, (box_id.clone(), address.clone()))?.unwrap_or(0);
+
+    Ok(RaffleTicketsResponse { box_id, address, tickets, total: fortune_box.opened })
+}
+
+pub fn query_jackpots(deps: Deps) -> StdResult<JackpotsResponse> {
+    let pools = JACKPOTS.range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, pool)| pool))
+        .collect::<StdResult<_>>()?;
+
+    Ok(JackpotsResponse { pools })
+}
+
+pub fn query_jackpot_history(
+    deps: Deps,
+    start_before: Option<u64>,
+    limit: Option<u32>
+) -> StdResult<JackpotHistoryResponse> {
+    let limit = limit.unwrap_or(30).min(100) as usize;
+    let wins = JACKPOT_WINS.range(
+        deps.storage,
+        None,
+        start_before.map(Bound::exclusive),
+        Order::Descending
+    )
+        .take(limit)
+        .map(|item| item.map(|(_, win)| win))
+        .collect::<StdResult<_>>()?;
+
+    Ok(JackpotHistoryResponse { wins })
+}
+
+pub fn query_featured(deps: Deps, env: Env) -> StdResult<FeaturedResponse> {
+    let now = env.block.time.seconds();
+    let mut boxes: Vec<FeaturedBox> = FEATURED.range(deps.storage, None, None, Order::Ascending)
+        .filter_map(|item| {
+            match item {
+                Ok((box_id, featured_until)) if featured_until > now =>
+                    Some(Ok(FeaturedBox { box_id, featured_until })),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            }
+        })
+        .collect::<StdResult<_>>()?;
+    boxes.sort_by_key(|featured| featured.featured_until);
+
+    Ok(FeaturedResponse { boxes })
+}
+
+pub fn query_auctions(deps: Deps, env: Env, open_only: bool) -> StdResult<AuctionsResponse> {
+    let now = env.block.time.seconds();
+    let auctions = AUCTIONS.range(deps.storage, None, None, Order::Descending)
+        .map(|item| item.map(|(_, v)| v))
+        .filter(|item| {
+            match item {
+                Ok(auction) if open_only => !auction.settled && now < auction.end_time,
+                _ => true,
+            }
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(AuctionsResponse { auctions })
+}
+
+pub fn query_auction_bids(deps: Deps, auction_id: u64) -> StdResult<AuctionBidsResponse> {
+    Ok(AuctionBidsResponse {
+        auction_id,
+        bids: auction_bids(deps.storage, auction_id)?,
+    })
+}
+
+pub fn query_treasury(deps: Deps) -> StdResult<TreasuryResponse> {
+    let balances = TREASURY.range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            item.map(|(denom, balance)| TreasuryEntry {
+                denom,
+                collected: balance.collected,
+                withdrawn: balance.withdrawn,
+                available: balance.collected - balance.withdrawn,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(TreasuryResponse { balances })
+}
+
+pub fn query_allowed_assets(deps: Deps) -> StdResult<AllowedAssetsResponse> {
+    let assets = ALLOWED_ASSETS.range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, asset)| asset))
+        .collect::<StdResult<Vec<AllowedAsset>>>()?;
+
+    Ok(AllowedAssetsResponse { assets })
+}
@@ -1,10 +1,22 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{ StdError, Uint128 };
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ContractError {
     #[error("{0}")] Std(#[from] StdError),
     #[error("InvalidCw721Token")] InvalidCw721Token {},
+    #[error("InvalidCw20Token")] InvalidCw20Token {},
+    #[error("Invalid {field} address: {address}")] InvalidAddress {
+        field: String,
+        address: String,
+    },
+    #[error("Asset not allowed: {asset}")] AssetNotAllowed {
+        asset: String,
+    },
+    #[error("Decimals are required for native assets")] MissingDecimals {},
+    #[error("Decimals do not match, expected {expected}")] DecimalsMismatch {
+        expected: u64,
+    },
     #[error("Unauthorized")] Unauthorized {},
     #[error("Max Odds Reached")] MaxOddsReached {
         msg: String,
@@ -13,6 +25,76 @@ pub enum ContractError {
     #[error("Amount not match")] AmountNotMatch {},
     #[error("Payment Failed")] PaymentFailed {},
     #[error("Conflict ID")] ConflictID {},
+    #[error("Invalid box id: {id}")] InvalidBoxId {
+        id: String,
+    },
     #[error("Reward not found")] RewardNotFound {},
     #[error("Box terminated")] BoxTerminated {},
+    #[error("Box locked after publish")] BoxLocked {},
+    #[error("Invalid merkle root")] InvalidMerkleRoot {},
+    #[error("Address not on the box allowlist")] NotAllowlisted {},
+    #[error("Open limit reached")] OpenLimitReached {},
+    #[error("Daily open limit reached")] DailyLimitReached {},
+    #[error("Box open cap reached")] BoxOpenCapReached {},
+    #[error("Box opens with a key")] KeyRequired {},
+    #[error("Invalid key")] InvalidKey {},
+    #[error("Invalid merkle proof")] InvalidProof {},
+    #[error("Credit drop not found")] DropNotFound {},
+    #[error("Credit drop expired")] DropExpired {},
+    #[error("Credits already claimed")] AlreadyClaimed {},
+    #[error("Credit count must be positive and fit in a u64")] InvalidCreditCount {},
+    #[error("Openers can not refer themselves")] SelfReferral {},
+    #[error("Referral share can not exceed {max} bps")] InvalidReferralShare {
+        max: u64,
+    },
+    #[error("Nothing to claim")] NothingToClaim {},
+    #[error("Invalid jackpot config")] InvalidJackpotConfig {},
+    #[error("Raffles need a duration and can not be keyed")] InvalidRaffle {},
+    #[error("Box is not a raffle")] NotARaffle {},
+    #[error("Raffle sold out")] RaffleFull {},
+    #[error("Raffle has not ended")] RaffleNotEnded {},
+    #[error("Invalid trade-in")] InvalidTradeIn {},
+    #[error("Box is opened by trading in NFTs")] TradeInRequired {},
+    #[error("Invalid reroll config")] InvalidReroll {},
+    #[error("Box does not allow rerolls")] RerollNotEnabled {},
+    #[error("No pending reward")] NoPendingReward {},
+    #[error("Reroll window has passed")] RerollExpired {},
+    #[error("Reroll window is still open")] RerollWindowOpen {},
+    #[error("Invalid bundle")] InvalidBundle {},
+    #[error("Bundle not found")] BundleNotFound {},
+    #[error("Box has bundles that are not fully deposited")] BundleIncomplete {},
+    #[error("Invalid series")] InvalidSeries {},
+    #[error("Series not found")] SeriesNotFound {},
+    #[error("Rewards of this box belong to a series")] SeriesBox {},
+    #[error("Series still has boxes that can be opened")] SeriesOpen {},
+    #[error("Opener does not hold any of the required tokens")] NotEligible {},
+    #[error("Box is not live: {status}")] BoxNotLive {
+        status: String,
+    },
+    #[error("Box paused")] BoxPaused {},
+    #[error("Invalid number of slots")] InvalidSlots {},
+    #[error("Resume time must be in the future")] InvalidResumeTime {},
+    #[error("Duration can not exceed {max} seconds")] InvalidDuration {
+        max: u64,
+    },
+    #[error("Start time is too far in the future")] InvalidStartTime {},
+    #[error("All featured slots are taken")] FeaturedSlotsFull {},
+    #[error("Insufficient treasury balance")] InsufficientTreasury {},
+    #[error("Withdrawal would touch escrowed funds")] EscrowViolation {},
+    #[error("Auction not found")] AuctionNotFound {},
+    #[error("Auction closed")] AuctionClosed {},
+    #[error("Auction still running")] AuctionRunning {},
+    #[error("Bid too low, must be at least {min}")] BidTooLow {
+        min: Uint128,
+    },
+    #[error("Box has nothing to draw")] EmptyBox {},
+    #[error("Cannot move box from {from} to {to}")] InvalidTransition {
+        from: String,
+        to: String,
+    },
+    #[error("Reward cannot be topped up")] InvalidTopUp {},
+    #[error("Max odds must be between {min} and {max}")] InvalidMaxOdds {
+        min: u64,
+        max: u64,
+    },
 }
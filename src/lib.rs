@@ -1,5 +1,7 @@
+pub mod asset;
 pub mod contract;
 mod error;
+pub mod merkle;
 pub mod msg;
 pub mod state;
 pub mod test;
@@ -0,0 +1,70 @@
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+/// Leaf committing to an address and its optional per-address open limit.
+pub fn leaf_hash(address: &str, limit: Option<u64>) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(address.as_bytes());
+    if let Some(limit) = limit {
+        hasher.update(b":");
+        hasher.update(limit.to_string().as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// Pairs are hashed in sorted order so proofs do not need to carry left/right flags.
+pub fn hash_pair(a: &Hash, b: &Hash) -> Hash {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update(first);
+    hasher.update(second);
+    hasher.finalize().into()
+}
+
+pub fn verify(root: &Hash, leaf: Hash, proof: &[Hash]) -> bool {
+    proof
+        .iter()
+        .fold(leaf, |node, sibling| hash_pair(&node, sibling))
+        == *root
+}
+
+/// Builds roots and proofs off-chain, an odd node at the end of a layer moves up unchanged.
+pub struct MerkleTree {
+    layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn new(leaves: Vec<Hash>) -> Self {
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let next = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hash_pair(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            layers.push(next);
+        }
+        MerkleTree { layers }
+    }
+
+    pub fn root(&self) -> Option<Hash> {
+        self.layers.last().unwrap().first().copied()
+    }
+
+    pub fn proof(&self, mut index: usize) -> Vec<Hash> {
+        let mut proof = Vec::new();
+        for layer in self.layers.iter().take(self.layers.len() - 1) {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        proof
+    }
+}
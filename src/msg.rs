@@ -1,66 +1,413 @@
 use cosmwasm_schema::{ cw_serde, QueryResponses };
-use cosmwasm_std::{ Addr, Uint128 };
+use cosmwasm_std::{ Addr, Decimal, Decimal256, HexBinary, Uint128 };
 use cw20::Cw20ReceiveMsg;
 use cw721::Cw721ReceiveMsg;
 
-use crate::state::{ UserInfo, FortuneBox };
+use crate::asset::{ AllowedAsset, Asset, AssetInfo };
+use crate::state::{
+    UserInfo,
+    FortuneBox,
+    FeatureAuction,
+    FeatureBid,
+    OpenRecord,
+    CreditGrant,
+    ReferralStats,
+    JackpotPool,
+    JackpotWin,
+    PendingReward,
+    PendingBundle,
+    Series,
+};
 
 #[cw_serde]
 pub struct InstantiateMsg {
-    pub owner: Addr,
+    pub owner: String,
     pub native_token: String,
-    pub founder_addr: Addr,
-    pub dev_addr: Addr,
+    pub founder_addr: String,
+    pub dev_addr: String,
 }
 
 #[cw_serde]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
-    CreateBox {
-        box_id: String,
-        price: Uint128,
-        token_denom: String,
-        token_decimals: u64,
-        token_type: String,
-        duration: u64,
-    },
+    CreateBox(CreateBoxMsg),
     ReceiveNft(Cw721ReceiveMsg),
     Receive(Cw20ReceiveMsg),
     AddTokenFactoryReward {
         box_id: String,
         rewards: Vec<TokenFactoryReward>,
     },
+    /// Declares a bundle reward, it joins the box once every item is deposited through
+    /// `DepositBundle` or the cw20 and cw721 hooks.
+    AddBundleReward {
+        box_id: String,
+        odds: u64,
+        items: Vec<BundleItem>,
+    },
+    /// Deposits native tokens of a declared bundle.
+    DepositBundle {
+        box_id: String,
+        bundle_id: u64,
+    },
+    /// Drops a bundle that is not complete yet, returning what was deposited.
+    CancelBundle {
+        box_id: String,
+        bundle_id: u64,
+    },
+    UpdateRewardOdds {
+        box_id: String,
+        reward_id: u64,
+        odds: u64,
+    },
+    RemoveReward {
+        box_id: String,
+        reward_id: u64,
+    },
+    TopUpReward {
+        box_id: String,
+        reward_id: u64,
+        count: u64,
+    },
+    FeatureBox {
+        box_id: String,
+        /// Number of `feature_period`s to buy, paid `feature_fees` each.
+        slots: u64,
+    },
+    OpenFeatureAuction {
+        slots: u64,
+        min_bid: Uint128,
+        /// Seconds the auction accepts bids for.
+        duration: u64,
+        /// Seconds winning boxes stay featured after settlement.
+        feature_duration: u64,
+    },
+    BidFeatureAuction {
+        auction_id: u64,
+        box_id: String,
+    },
+    SettleFeatureAuction {
+        auction_id: u64,
+    },
+    UpdateFeatureConfig {
+        feature_fees: Uint128,
+        feature_period: u64,
+        max_featured: u64,
+    },
+    WithdrawTreasury {
+        asset: AssetInfo,
+        amount: Uint128,
+        recipient: String,
+    },
+    RegisterAsset {
+        info: AssetInfo,
+        /// Required for native denoms, checked against the token info for cw20s.
+        decimals: Option<u64>,
+    },
+    RemoveAsset {
+        info: AssetInfo,
+    },
+    UpdateOddsBounds {
+        min_box_odds: u64,
+        max_box_odds: u64,
+    },
     OpenBox {
         box_id: String,
+        /// Required when the box has an allowlist.
+        proof: Option<AllowlistProof>,
+        /// Earns a share of the protocol fee on paid opens.
+        referrer: Option<String>,
+    },
+    /// Share of the protocol fee paid to referrers, in basis points.
+    UpdateReferralShare {
+        referral_bps: u64,
+    },
+    ClaimReferralRewards {},
+    UpdateJackpotConfig {
+        /// Share of every paid open fed into the jackpot, in basis points.
+        jackpot_bps: u64,
+        /// Chance of winning the jackpot per paid open, out of `util::JACKPOT_ODDS_SCALE`.
+        jackpot_odds: u64,
+    },
+    /// Sets the balance the treasury refills the jackpot of `asset` to after a win.
+    SetJackpotSeed {
+        asset: AssetInfo,
+        seed: Uint128,
+    },
+    /// Returns the NFTs deposited towards an unfinished trade-in.
+    WithdrawTradeIn {
+        box_id: String,
+    },
+    /// Puts the pending reward back and draws again, paying the reroll fee.
+    Reroll {
+        box_id: String,
+    },
+    /// Takes the pending reward. Anyone may finalize the reward of `address` once its reroll
+    /// window has passed.
+    Accept {
+        box_id: String,
+        address: Option<String>,
+    },
+    /// Draws the winners of an ended raffle, callable by anyone.
+    DrawRaffle {
+        box_id: String,
+    },
+    SetAllowlist {
+        box_id: String,
+        /// Sha256 merkle root over the allowed addresses, see `merkle::leaf_hash`. Unset opens
+        /// the box to everyone.
+        root: Option<HexBinary>,
+    },
+    /// Grants free opens, by the box creator for box scopes, by the creator for creator scopes,
+    /// or by the owner for either.
+    IssueCredits {
+        scope: CreditScope,
+        recipients: Vec<CreditRecipient>,
+        expires_at: Option<u64>,
+    },
+    /// Lets addresses claim free opens with a proof, leaves are `merkle::leaf_hash(address, count)`.
+    CreateCreditDrop {
+        scope: CreditScope,
+        root: HexBinary,
+        expires_at: Option<u64>,
+    },
+    ClaimCredits {
+        drop_id: u64,
+        count: u64,
+        proof: Vec<HexBinary>,
     },
     CancelBox {
         box_id: String,
     },
+    /// Moves the rewards of draft box `box_id` into a new series, the box stays in it as the
+    /// first of the boxes sharing that stock.
+    CreateSeries {
+        series_id: String,
+        box_id: String,
+    },
+    /// Points a draft box without rewards of its own at the series stock. Calling it again
+    /// before publishing replaces the odds overrides.
+    JoinSeries {
+        series_id: String,
+        box_id: String,
+        odds: Vec<SeriesOdds>,
+    },
+    /// Returns what is left of the series stock once none of its boxes can be opened.
+    CloseSeries {
+        series_id: String,
+    },
+    PublishBox {
+        box_id: String,
+        /// Unix seconds the box goes live at, defaults to immediately.
+        start_time: Option<u64>,
+    },
+    PauseBox {
+        box_id: String,
+        /// Unix seconds the box resumes by itself at, stays paused until `ResumeBox` if unset.
+        resume_at: Option<u64>,
+    },
+    ResumeBox {
+        box_id: String,
+    },
+}
+
+#[cw_serde]
+pub struct CreateBoxMsg {
+    /// Between 3 and 32 lowercase letters, digits, `-` or `_`, not only digits. A numeric id
+    /// is issued by the contract when unset and returned in the response data.
+    pub box_id: Option<String>,
+    /// Price of one open, its asset has to be in the allowed asset registry.
+    pub price: Asset,
+    pub duration: u64,
+    /// Odds denominator of the box, defaults to the config `max_odds`.
+    pub max_odds: Option<u64>,
+    /// How reward odds are turned into draw probabilities, defaults to `OddsModel::Fixed`.
+    pub odds_model: Option<OddsModel>,
+    /// Lets the part of `max_odds` not covered by rewards draw nothing instead of a reward.
+    pub allow_empty: Option<bool>,
+    /// Points credited to the opener whenever a draw comes up empty.
+    pub consolation_points: Option<Uint128>,
+    /// Merkle root of the addresses allowed to open the box.
+    pub allowlist_root: Option<HexBinary>,
+    /// Openers have to meet at least one of these, anyone may open when empty.
+    pub holder_gate: Option<Vec<HolderRequirement>>,
+    pub open_limits: Option<OpenLimits>,
+    /// Makes opening take a key token instead of paying the price.
+    pub key: Option<BoxKey>,
+    pub kind: Option<BoxKind>,
+    /// Makes opening take NFTs of other collections instead of paying the price.
+    pub trade_in: Option<TradeIn>,
+    pub reroll: Option<RerollConfig>,
+}
+
+/// Odds a series box gives one of the series rewards instead of the series odds.
+#[cw_serde]
+pub struct SeriesOdds {
+    pub reward_id: u64,
+    pub odds: u64,
+}
+
+/// Holds won rewards for `window` seconds, during which the opener may pay `fee` in the box
+/// price asset to put the reward back and draw again.
+#[cw_serde]
+pub struct RerollConfig {
+    pub fee: Uint128,
+    pub window: u64,
+}
+
+/// Key spent on every open of a box. Used keys are burned, or held by the contract and handed
+/// to the creator once the box closes.
+#[cw_serde]
+pub enum BoxKey {
+    /// One token of the cw721 collection, sent with `NftReceiveMsg::OpenBox`.
+    Nft {
+        collection: String,
+        burn: bool,
+    },
+    /// `amount` of the cw20, sent with `TokenReceiveMsg::OpenBoxWithKey`.
+    Cw20 {
+        token: String,
+        amount: Uint128,
+        burn: bool,
+    },
+}
+
+/// Boxes a free-open credit can be spent on.
+#[cw_serde]
+pub enum CreditScope {
+    Box {
+        box_id: String,
+    },
+    /// Any box of the creator.
+    Creator {
+        creator: String,
+    },
+}
+
+#[cw_serde]
+pub struct CreditRecipient {
+    pub address: String,
+    pub count: u64,
+}
+
+/// Opens paid by handing in `count` NFTs of the listed collections, deposited one at a time.
+/// They are burned, or sent to `sink` when set.
+#[cw_serde]
+pub struct TradeIn {
+    pub collections: Vec<String>,
+    pub count: u64,
+    pub sink: Option<String>,
+}
+
+#[cw_serde]
+pub struct TradedNft {
+    pub collection: String,
+    pub token_id: String,
+}
+
+/// Key handed over for one open.
+#[cw_serde]
+pub enum KeyUsed {
+    Nft {
+        collection: String,
+        token_id: String,
+    },
+    Cw20 {
+        token: String,
+        amount: Uint128,
+    },
+}
+
+/// Caps on how often a box can be opened, unset fields are unlimited.
+#[cw_serde]
+#[derive(Default)]
+pub struct OpenLimits {
+    /// Opens per address over the life of the box.
+    pub per_address: Option<u64>,
+    /// Opens per address per UTC day.
+    pub per_address_daily: Option<u64>,
+    /// Opens of the box overall, it sells out once reached whatever stock is left.
+    pub total: Option<u64>,
+}
+
+#[cw_serde]
+pub enum HolderRequirement {
+    /// Holds at least one token of the cw721 collection.
+    Nft {
+        collection: String,
+    },
+    /// Holds at least `asset.amount` of the asset.
+    Balance {
+        asset: Asset,
+    },
+}
+
+/// Inclusion proof of the opener in a box allowlist. `limit` has to match the leaf, it caps
+/// how many times the address may open the box.
+#[cw_serde]
+pub struct AllowlistProof {
+    pub limit: Option<u64>,
+    pub proof: Vec<HexBinary>,
 }
 
 #[cw_serde]
 pub enum NftReceiveMsg {
     AddNftReward {
         box_id: String,
-        collection_addr: Addr,
+        collection_addr: String,
         nft_id: String,
         odds: u64,
     },
+    /// Opens a box keyed to the sending collection with the received token.
+    OpenBox {
+        box_id: String,
+        proof: Option<AllowlistProof>,
+    },
+    DepositBundle {
+        box_id: String,
+        bundle_id: u64,
+    },
+    /// Deposits the received token towards a trade-in, the deposit completing it opens the box.
+    TradeIn {
+        box_id: String,
+        proof: Option<AllowlistProof>,
+    },
 }
 
 #[cw_serde]
 pub enum TokenReceiveMsg {
     CreateFortuneBoxWithCoin {
         box_id: String,
-        creator: Addr,
+        creator: String,
         denom: String,
         amount: Uint128,
         odds: u64,
-        price: Uint128,
-        token_denom: String,
-        token_decimals: u64,
-        token_type: String,
+        price: Asset,
         duration: u64,
     },
+    /// Opens a box priced in the sending cw20.
+    OpenBox {
+        box_id: String,
+        proof: Option<AllowlistProof>,
+        referrer: Option<String>,
+    },
+    DepositBundle {
+        box_id: String,
+        bundle_id: u64,
+    },
+    /// Rerolls on a box priced in the sending cw20.
+    Reroll {
+        box_id: String,
+    },
+    /// Opens a box keyed to the sending cw20.
+    OpenBoxWithKey {
+        box_id: String,
+        proof: Option<AllowlistProof>,
+    },
+    TopUpReward {
+        box_id: String,
+        reward_id: u64,
+        count: u64,
+    },
 }
 
 #[cw_serde]
@@ -80,11 +427,13 @@ pub struct RewardData {
     pub reward_type: RewardType,
     pub collection_addr: Option<Addr>,
     pub nft_id: Option<String>,
-    pub denom: Option<String>,
-    pub amount: Option<Uint128>,
+    /// Token and amount paid out per win, unset for NFT rewards.
+    pub asset: Option<Asset>,
     pub decimals: Option<u64>,
     pub odds: u64,
     pub count: u64,
+    /// Items paid out together on a win, only set for `Bundle` rewards.
+    pub bundle: Vec<BundleItem>,
 }
 
 #[cw_serde]
@@ -92,6 +441,62 @@ pub enum RewardType {
     Cw20,
     Nft,
     TokenFactory,
+    Bundle,
+}
+
+/// One component of a bundle reward.
+#[cw_serde]
+pub enum BundleItem {
+    Nft {
+        collection: String,
+        token_id: String,
+    },
+    Token {
+        asset: Asset,
+    },
+}
+
+/// Fixed keeps every reward at its configured odds until it runs out, StockWeighted
+/// multiplies the odds by the remaining count so scarce rewards get rarer as they sell.
+#[cw_serde]
+#[derive(Default)]
+pub enum OddsModel {
+    #[default]
+    Fixed,
+    StockWeighted,
+}
+
+/// Instant boxes draw a reward on every open. Raffle opens buy tickets instead and
+/// `DrawRaffle` picks the winners once the box ends, refunding every ticket when fewer than
+/// `min_tickets` were sold.
+#[cw_serde]
+#[derive(Default)]
+pub enum BoxKind {
+    #[default]
+    Instant,
+    Raffle {
+        min_tickets: Option<u64>,
+        /// Tickets on sale, up to and by default `MAX_RAFFLE_TICKETS` so the draw fits in one call.
+        max_tickets: Option<u64>,
+    },
+}
+
+/// Lifecycle of a box. Rewards can only be changed while in `Draft`, opens only happen while
+/// `Live`, and `SoldOut`, `Cancelled` and `Expired` are final apart from reclaiming an expired box.
+#[cw_serde]
+pub enum BoxStatus {
+    Draft,
+    Scheduled,
+    Live,
+    Paused,
+    SoldOut,
+    Cancelled,
+    Expired,
+}
+
+#[cw_serde]
+pub struct CreateBoxResponse {
+    pub box_id: String,
 }
 
 #[cw_serde]
@@ -110,12 +515,231 @@ pub struct ConfigResponse {
     pub token: String,
     pub injscribed_address: Addr,
     pub feature_fees: Uint128,
+    pub feature_period: u64,
+    pub max_featured: u64,
+    pub max_odds: u64,
+    pub min_box_odds: u64,
+    pub max_box_odds: u64,
+    pub referral_bps: u64,
+    pub jackpot_bps: u64,
+    pub jackpot_odds: u64,
+}
+
+#[cw_serde]
+pub struct RewardOdds {
+    pub reward_id: u64,
+    pub odds: u64,
+    pub count: u64,
+    /// Configured odds as a fraction of the box `max_odds`.
+    pub share: Decimal,
+    pub probability: Decimal,
+}
+
+#[cw_serde]
+pub struct BoxOddsResponse {
+    pub box_id: String,
+    pub odds_model: OddsModel,
+    pub max_odds: u64,
+    pub rewards: Vec<RewardOdds>,
+    pub empty_probability: Decimal,
+}
+
+#[cw_serde]
+pub struct DenomValue {
+    pub denom: String,
+    pub amount: Decimal256,
+}
+
+#[cw_serde]
+pub struct ExpectedValueResponse {
+    pub box_id: String,
+    pub price: Asset,
+    pub win_probability: Decimal,
+    pub nft_probability: Decimal,
+    pub tokens: Vec<DenomValue>,
+    pub points: Decimal256,
+}
+
+#[cw_serde]
+pub struct OpenAllowanceResponse {
+    pub box_id: String,
+    pub address: Addr,
+    pub opened: u64,
+    pub opened_today: u64,
+    /// Opens left under each limit and overall, `None` when unlimited. Allowlist limits are
+    /// not included as they are only known from the proof.
+    pub remaining_for_address: Option<u64>,
+    pub remaining_today: Option<u64>,
+    pub remaining_total: Option<u64>,
+    pub remaining: Option<u64>,
+}
+
+#[cw_serde]
+pub struct SeriesResponse {
+    pub series: Series,
+}
+
+#[cw_serde]
+pub struct PendingBundlesResponse {
+    pub bundles: Vec<PendingBundle>,
+}
+
+#[cw_serde]
+pub struct PendingRewardResponse {
+    pub pending: Option<PendingReward>,
+}
+
+#[cw_serde]
+pub struct TradeInDepositsResponse {
+    pub deposits: Vec<TradedNft>,
+    pub required: u64,
+}
+
+#[cw_serde]
+pub struct RaffleTicketsResponse {
+    pub box_id: String,
+    pub address: Addr,
+    pub tickets: u64,
+    pub total: u64,
+}
+
+#[cw_serde]
+pub struct JackpotsResponse {
+    pub pools: Vec<JackpotPool>,
+}
+
+#[cw_serde]
+pub struct JackpotHistoryResponse {
+    pub wins: Vec<JackpotWin>,
+}
+
+#[cw_serde]
+pub struct ReferralStatsResponse {
+    pub stats: ReferralStats,
+    pub claimable: Vec<Asset>,
+}
+
+#[cw_serde]
+pub struct ReferralLeaderboardResponse {
+    pub referrers: Vec<ReferralStats>,
+}
+
+#[cw_serde]
+pub struct CreditsResponse {
+    pub address: Addr,
+    pub credits: Vec<CreditGrant>,
+}
+
+#[cw_serde]
+pub struct OpenHistoryResponse {
+    pub opens: Vec<OpenRecord>,
+}
+
+#[cw_serde]
+pub struct FeaturedBox {
+    pub box_id: String,
+    pub featured_until: u64,
+}
+
+#[cw_serde]
+pub struct FeaturedResponse {
+    pub boxes: Vec<FeaturedBox>,
+}
+
+#[cw_serde]
+pub struct TreasuryEntry {
+    pub denom: String,
+    pub collected: Uint128,
+    pub withdrawn: Uint128,
+    pub available: Uint128,
+}
+
+#[cw_serde]
+pub struct TreasuryResponse {
+    pub balances: Vec<TreasuryEntry>,
+}
+
+#[cw_serde]
+pub struct AllowedAssetsResponse {
+    pub assets: Vec<AllowedAsset>,
+}
+
+#[cw_serde]
+pub struct AuctionsResponse {
+    pub auctions: Vec<FeatureAuction>,
+}
+
+#[cw_serde]
+pub struct AuctionBidsResponse {
+    pub auction_id: u64,
+    pub bids: Vec<FeatureBid>,
 }
 
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
     #[returns(ConfigResponse)] GetConfig {},
-    #[returns(BoxesResponse)] GetBoxes {},
+    #[returns(BoxesResponse)] GetBoxes {
+        status: Option<BoxStatus>,
+    },
     #[returns(UsersInfoResponse)] GetUsers {},
+    #[returns(BoxOddsResponse)] GetBoxOdds {
+        box_id: String,
+    },
+    #[returns(ExpectedValueResponse)] GetExpectedValue {
+        box_id: String,
+    },
+    #[returns(OpenAllowanceResponse)] GetOpenAllowance {
+        box_id: String,
+        address: String,
+    },
+    #[returns(OpenHistoryResponse)] GetOpenHistory {
+        box_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(CreditsResponse)] GetCredits {
+        address: String,
+    },
+    #[returns(ReferralStatsResponse)] GetReferralStats {
+        address: String,
+    },
+    /// Ranks referrers by earnings in `asset`, or by referral count when unset.
+    #[returns(ReferralLeaderboardResponse)] GetReferralLeaderboard {
+        asset: Option<AssetInfo>,
+        limit: Option<u32>,
+    },
+    #[returns(SeriesResponse)] GetSeries {
+        series_id: String,
+    },
+    #[returns(PendingBundlesResponse)] GetPendingBundles {
+        box_id: String,
+    },
+    #[returns(PendingRewardResponse)] GetPendingReward {
+        box_id: String,
+        address: String,
+    },
+    #[returns(TradeInDepositsResponse)] GetTradeInDeposits {
+        box_id: String,
+        address: String,
+    },
+    #[returns(RaffleTicketsResponse)] GetRaffleTickets {
+        box_id: String,
+        address: String,
+    },
+    #[returns(JackpotsResponse)] GetJackpots {},
+    /// Jackpot wins, newest first.
+    #[returns(JackpotHistoryResponse)] GetJackpotHistory {
+        start_before: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(FeaturedResponse)] ListFeatured {},
+    #[returns(TreasuryResponse)] GetTreasury {},
+    #[returns(AllowedAssetsResponse)] GetAllowedAssets {},
+    #[returns(AuctionsResponse)] ListAuctions {
+        open_only: Option<bool>,
+    },
+    #[returns(AuctionBidsResponse)] GetAuctionBids {
+        auction_id: u64,
+    },
 }
@@ -1,10 +1,24 @@
 use std::collections::HashMap;
 
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{ Addr, Uint128 };
+use cosmwasm_std::{ Addr, HexBinary, Uint128 };
 use cw_storage_plus::{ Item, Map };
 
-use crate::msg::RewardData;
+use crate::asset::{ AllowedAsset, Asset };
+use crate::msg::{
+    BoxKey,
+    BoxKind,
+    BoxStatus,
+    CreditScope,
+    HolderRequirement,
+    KeyUsed,
+    OddsModel,
+    OpenLimits,
+    RerollConfig,
+    RewardData,
+    TradeIn,
+    TradedNft,
+};
 
 #[cw_serde]
 pub struct Config {
@@ -13,8 +27,18 @@ pub struct Config {
     pub native_token: String,
     pub injscribed_address: Addr,
     pub feature_fees: Uint128,
+    pub feature_period: u64,
+    pub max_featured: u64,
     pub max_odds: u64,
+    pub min_box_odds: u64,
+    pub max_box_odds: u64,
     pub enabled: bool,
+    /// Share of the protocol fee paid to referrers, in basis points.
+    pub referral_bps: u64,
+    /// Share of every paid open fed into the jackpot pool of its asset, in basis points.
+    pub jackpot_bps: u64,
+    /// Chance of a paid open winning the jackpot, out of `util::JACKPOT_ODDS_SCALE`.
+    pub jackpot_odds: u64,
 }
 
 #[cw_serde]
@@ -23,16 +47,62 @@ pub struct FortuneBox {
     pub creator: Addr,
     pub rewards: Vec<RewardData>,
     pub max_odds: u64,
-    pub price: Uint128,
-    pub token_denom: String,
-    pub token_decimals: u64,
-    pub token_type: String,
+    pub price: Asset,
+    pub price_decimals: u64,
     pub duration: u64,
-    pub is_over: bool,
-    pub is_featured: bool,
+    pub status: BoxStatus,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    pub resume_at: Option<u64>,
+    pub odds_model: OddsModel,
+    pub allow_empty: bool,
+    pub consolation_points: Uint128,
+    pub opened: u64,
+    pub featured_until: Option<u64>,
+    pub allowlist_root: Option<HexBinary>,
+    pub holder_gate: Vec<HolderRequirement>,
+    pub open_limits: OpenLimits,
+    pub key: Option<BoxKey>,
+    /// Unburned NFT keys held until the box closes.
+    pub held_keys: Vec<String>,
+    /// Unburned cw20 keys held until the box closes.
+    pub held_key_amount: Uint128,
+    pub kind: BoxKind,
+    /// Ticket sales of a raffle, held until the draw.
+    pub raffle_pot: Uint128,
+    pub trade_in: Option<TradeIn>,
+    pub reroll: Option<RerollConfig>,
+    /// Series whose stock the rewards share, their counts mirror the series.
+    pub series: Option<String>,
     pub winners: Option<Vec<WinnerStruct>>,
 }
 
+#[cw_serde]
+pub struct FeatureAuction {
+    pub id: u64,
+    pub slots: u64,
+    pub denom: String,
+    pub min_bid: Uint128,
+    pub end_time: u64,
+    /// Seconds winning boxes stay featured once the auction settles.
+    pub feature_duration: u64,
+    pub settled: bool,
+}
+
+#[cw_serde]
+pub struct FeatureBid {
+    pub box_id: String,
+    pub bidder: Addr,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct TreasuryBalance {
+    pub collected: Uint128,
+    pub withdrawn: Uint128,
+}
+
 #[cw_serde]
 pub struct WinnerStruct {
     pub address: Addr,
@@ -47,6 +117,97 @@ pub struct UserInfo {
     pub tokens_spent: Uint128,
     pub box_opened: u64,
     pub rewards: HashMap<String, Vec<u64>>,
+    pub points: Uint128,
+}
+
+/// One open of a box, `index` counts the opens of the box from 0.
+#[cw_serde]
+pub struct OpenRecord {
+    pub index: u64,
+    pub opener: Addr,
+    pub time: u64,
+    pub reward_id: Option<u64>,
+    pub payment: Option<Asset>,
+    pub key: Option<KeyUsed>,
+    /// Scope of the credit spent instead of a payment.
+    pub credit: Option<CreditScope>,
+    pub traded_in: Vec<TradedNft>,
+}
+
+/// Reward stock shared by several boxes, each drawing from it with its own odds.
+#[cw_serde]
+pub struct Series {
+    pub id: String,
+    pub creator: Addr,
+    pub rewards: Vec<RewardData>,
+    pub boxes: Vec<String>,
+}
+
+/// Bundle reward waiting for its items, `deposited` follows the order of `reward.bundle`.
+#[cw_serde]
+pub struct PendingBundle {
+    pub reward: RewardData,
+    pub deposited: Vec<bool>,
+}
+
+/// Won reward held by the contract while the opener may still reroll it, `reward` has a
+/// count of 1.
+#[cw_serde]
+pub struct PendingReward {
+    pub box_id: String,
+    pub opener: Addr,
+    pub open_index: u64,
+    pub reward: RewardData,
+    pub expires_at: u64,
+    pub rerolls: u64,
+}
+
+/// Free opens an address holds in a scope, gone after `expires_at`.
+#[cw_serde]
+pub struct CreditGrant {
+    pub scope: CreditScope,
+    pub count: u64,
+    pub expires_at: Option<u64>,
+}
+
+#[cw_serde]
+pub struct CreditDrop {
+    pub id: u64,
+    pub issuer: Addr,
+    pub scope: CreditScope,
+    pub root: HexBinary,
+    pub expires_at: Option<u64>,
+}
+
+/// Opens an address referred and what it earned from them, in every asset paid.
+#[cw_serde]
+pub struct ReferralStats {
+    pub address: Addr,
+    pub referrals: u64,
+    pub earned: Vec<Asset>,
+}
+
+/// Jackpot pool of one asset, refilled up to `seed` from the treasury after a win.
+#[cw_serde]
+pub struct JackpotPool {
+    pub balance: Asset,
+    pub seed: Uint128,
+}
+
+#[cw_serde]
+pub struct JackpotWin {
+    pub id: u64,
+    pub box_id: String,
+    pub winner: Addr,
+    pub prize: Asset,
+    pub time: u64,
+}
+
+/// Opens of one address in one box on `day`, counted in days since the unix epoch.
+#[cw_serde]
+pub struct DailyOpens {
+    pub day: u64,
+    pub count: u64,
 }
 
 pub const CONFIG_KEY: &str = "config";
@@ -55,7 +216,78 @@ pub const CONFIG: Item<Config> = Item::new(CONFIG_KEY);
 pub const ACCOUNT_MAP_PREFIX: &str = "account_map";
 pub const ACCOUNT_MAP: Map<Addr, UserInfo> = Map::new(ACCOUNT_MAP_PREFIX);
 
+pub const BOX_OPENS_PREFIX: &str = "box_opens";
+pub const BOX_OPENS: Map<(String, Addr), u64> = Map::new(BOX_OPENS_PREFIX);
+
+pub const DAILY_OPENS_PREFIX: &str = "daily_opens";
+pub const DAILY_OPENS: Map<(String, Addr), DailyOpens> = Map::new(DAILY_OPENS_PREFIX);
+
+pub const CREDITS_PREFIX: &str = "credits";
+pub const CREDITS: Map<(Addr, String), CreditGrant> = Map::new(CREDITS_PREFIX);
+
+pub const CREDIT_DROP_COUNT: Item<u64> = Item::new("credit_drop_count");
+
+pub const CREDIT_DROPS_PREFIX: &str = "credit_drops";
+pub const CREDIT_DROPS: Map<u64, CreditDrop> = Map::new(CREDIT_DROPS_PREFIX);
+
+pub const CREDIT_CLAIMS_PREFIX: &str = "credit_claims";
+pub const CREDIT_CLAIMS: Map<(u64, Addr), u64> = Map::new(CREDIT_CLAIMS_PREFIX);
+
+pub const REFERRALS_PREFIX: &str = "referrals";
+pub const REFERRALS: Map<Addr, ReferralStats> = Map::new(REFERRALS_PREFIX);
+
+/// Unclaimed referral earnings, keyed by referrer and asset key.
+pub const REFERRAL_BALANCES_PREFIX: &str = "referral_balances";
+pub const REFERRAL_BALANCES: Map<(Addr, String), Asset> = Map::new(REFERRAL_BALANCES_PREFIX);
+
+/// NFTs handed in towards a trade-in that is not complete yet.
+pub const TRADE_IN_DEPOSITS_PREFIX: &str = "trade_in_deposits";
+pub const TRADE_IN_DEPOSITS: Map<(String, Addr), Vec<TradedNft>> = Map::new(TRADE_IN_DEPOSITS_PREFIX);
+
+pub const SERIES_PREFIX: &str = "series";
+pub const SERIES: Map<String, Series> = Map::new(SERIES_PREFIX);
+
+pub const PENDING_BUNDLES_PREFIX: &str = "pending_bundles";
+pub const PENDING_BUNDLES: Map<(String, u64), PendingBundle> = Map::new(PENDING_BUNDLES_PREFIX);
+
+pub const PENDING_REWARDS_PREFIX: &str = "pending_rewards";
+pub const PENDING_REWARDS: Map<(String, Addr), PendingReward> = Map::new(PENDING_REWARDS_PREFIX);
+
+pub const OPEN_HISTORY_PREFIX: &str = "open_history";
+pub const OPEN_HISTORY: Map<(String, u64), OpenRecord> = Map::new(OPEN_HISTORY_PREFIX);
+
 pub const BOX_MAP_PREFIX: &str = "box_map";
 pub const BOX_MAP: Map<String, FortuneBox> = Map::new(BOX_MAP_PREFIX);
 
+pub const BOX_COUNT: Item<u64> = Item::new("box_count");
+
+pub const FEATURED_PREFIX: &str = "featured";
+pub const FEATURED: Map<String, u64> = Map::new(FEATURED_PREFIX);
+
+pub const AUCTION_COUNT: Item<u64> = Item::new("auction_count");
+
+pub const AUCTIONS_PREFIX: &str = "auctions";
+pub const AUCTIONS: Map<u64, FeatureAuction> = Map::new(AUCTIONS_PREFIX);
+
+pub const AUCTION_BIDS_PREFIX: &str = "auction_bids";
+pub const AUCTION_BIDS: Map<(u64, String), FeatureBid> = Map::new(AUCTION_BIDS_PREFIX);
+
+pub const TREASURY_PREFIX: &str = "treasury";
+pub const TREASURY: Map<String, TreasuryBalance> = Map::new(TREASURY_PREFIX);
+
+/// Funds held on behalf of others, keyed by asset key, see `util::escrowed_amount`.
+pub const ESCROW_PREFIX: &str = "escrow";
+pub const ESCROW: Map<String, Uint128> = Map::new(ESCROW_PREFIX);
+
+pub const JACKPOTS_PREFIX: &str = "jackpots";
+pub const JACKPOTS: Map<String, JackpotPool> = Map::new(JACKPOTS_PREFIX);
+
+pub const JACKPOT_WIN_COUNT: Item<u64> = Item::new("jackpot_win_count");
+
+pub const JACKPOT_WINS_PREFIX: &str = "jackpot_wins";
+pub const JACKPOT_WINS: Map<u64, JackpotWin> = Map::new(JACKPOT_WINS_PREFIX);
+
+pub const ALLOWED_ASSETS_PREFIX: &str = "allowed_assets";
+pub const ALLOWED_ASSETS: Map<String, AllowedAsset> = Map::new(ALLOWED_ASSETS_PREFIX);
+
 pub const NOIS_PROXY: Item<Addr> = Item::new("nois_proxy");
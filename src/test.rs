@@ -1,48 +1,1986 @@
 #[cfg(test)]
 mod test_module {
-    use cosmwasm_std::Uint128;
+    use cosmwasm_std::{
+        coins,
+        BankMsg,
+        Coin,
+        CosmosMsg,
+        testing::{
+            mock_dependencies,
+            mock_dependencies_with_balance,
+            mock_env,
+            mock_info,
+            MockApi,
+            MockQuerier,
+            MockStorage,
+        },
+        Addr,
+        Binary,
+        Decimal,
+        Decimal256,
+        HexBinary,
+        OwnedDeps,
+        Uint128,
+        WasmMsg,
+        WasmQuery,
+        SystemResult,
+        ContractResult,
+        to_json_binary,
+        from_json,
+    };
+    use cw20::{ BalanceResponse as Cw20BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg };
+    use cw721::{ Cw721ExecuteMsg, Cw721QueryMsg, Cw721ReceiveMsg, TokensResponse };
 
-    use crate::msg::{ TokenFactoryReward, RewardType };
+    use crate::contract::{
+        execute,
+        instantiate,
+        query_box_odds,
+        query_boxes,
+        query_expected_value,
+        query_featured,
+        query_auction_bids,
+        query_treasury,
+        query_allowed_assets,
+        query_open_allowance,
+        query_open_history,
+        query_credits,
+        query_referral_stats,
+        query_referral_leaderboard,
+        query_jackpots,
+        query_jackpot_history,
+        query_raffle_tickets,
+        query_trade_in_deposits,
+        query_pending_reward,
+        query_pending_bundles,
+        query_series,
+        query_users,
+    };
+    use crate::msg::{
+        TokenFactoryReward,
+        RewardType,
+        RewardData,
+        OddsModel,
+        BoxStatus,
+        InstantiateMsg,
+        ExecuteMsg,
+        CreateBoxMsg,
+        TokenReceiveMsg,
+        NftReceiveMsg,
+        CreateBoxResponse,
+        AllowlistProof,
+        HolderRequirement,
+        OpenLimits,
+        BoxKey,
+        KeyUsed,
+        CreditScope,
+        CreditRecipient,
+        BoxKind,
+        TradeIn,
+        TradedNft,
+        RerollConfig,
+        BundleItem,
+        SeriesOdds,
+    };
+    use crate::asset::{ AllowedAsset, Asset, AssetInfo };
+    use crate::ContractError;
+    use crate::state::{ CreditGrant, FortuneBox, ALLOWED_ASSETS, BOX_MAP };
+    use crate::util::{ self, DrawOutcome };
+    use crate::merkle;
 
-    #[test]
-    fn test() {
-        let mut weighted_list = Vec::new();
-        let mut rewards: Vec<_> = Vec::new();
-        rewards.push(TokenFactoryReward {
-            id: 1,
-            token_denom: "inj".to_string(),
-            amount: Uint128::from(10000000000000000u64),
-            token_decimals: 18,
-            odds: 10,
-            count: 12,
+    fn token_reward(id: u64, odds: u64, count: u64) -> RewardData {
+        RewardData {
+            id,
             reward_type: RewardType::TokenFactory,
-        });
-        rewards.push(TokenFactoryReward {
-            id: 2,
+            collection_addr: None,
+            nft_id: None,
+            asset: Some(Asset {
+                info: AssetInfo::Native { denom: "inj".to_string() },
+                amount: Uint128::from(1000u64),
+            }),
+            decimals: Some(18),
+            odds,
+            count,
+            bundle: vec![],
+        }
+    }
+
+    fn fortune_box(odds_model: OddsModel, rewards: Vec<RewardData>) -> FortuneBox {
+        FortuneBox {
+            id: "box".to_string(),
+            creator: Addr::unchecked("creator"),
+            rewards,
+            max_odds: 1000,
+            price: Asset {
+                info: AssetInfo::Native { denom: "inj".to_string() },
+                amount: Uint128::from(100u64),
+            },
+            price_decimals: 18,
+            duration: 0,
+            status: BoxStatus::Live,
+            start_time: Some(0),
+            end_time: None,
+            resume_at: None,
+            odds_model,
+            allow_empty: false,
+            consolation_points: Uint128::zero(),
+            opened: 0,
+            featured_until: None,
+            allowlist_root: None,
+            holder_gate: vec![],
+            open_limits: OpenLimits::default(),
+            key: None,
+            held_keys: vec![],
+            held_key_amount: Uint128::zero(),
+            kind: BoxKind::Instant,
+            raffle_pot: Uint128::zero(),
+            trade_in: None,
+            reroll: None,
+            series: None,
+            winners: None,
+        }
+    }
+
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        setup_with_balance(&[])
+    }
+
+    fn setup_with_balance(balance: &[Coin]) -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies_with_balance(balance);
+        let msg = InstantiateMsg {
+            owner: "owner".to_string(),
+            native_token: "inj".to_string(),
+            founder_addr: "founder".to_string(),
+            dev_addr: "dev".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        deps
+    }
+
+    fn create_box_msg(box_id: &str) -> CreateBoxMsg {
+        CreateBoxMsg {
+            box_id: Some(box_id.to_string()),
+            price: Asset {
+                info: AssetInfo::Native { denom: "inj".to_string() },
+                amount: Uint128::from(100u64),
+            },
+            duration: 0,
+            max_odds: None,
+            odds_model: None,
+            allow_empty: None,
+            consolation_points: None,
+            allowlist_root: None,
+            holder_gate: None,
+            open_limits: None,
+            key: None,
+            kind: None,
+            trade_in: None,
+            reroll: None,
+        }
+    }
+
+    /// Creates the box, funds `count` rewards of 10 inj at 100 odds and publishes it.
+    fn publish_box(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, msg: CreateBoxMsg, count: u64) {
+        let box_id = msg.box_id.clone().unwrap();
+        let creator = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(msg)).unwrap();
+        let rewards = vec![TokenFactoryReward {
+            id: 0,
+            odds: 100,
             token_denom: "inj".to_string(),
-            amount: Uint128::from(1000000000000000000u64),
             token_decimals: 18,
-            odds: 10,
-            count: 3,
             reward_type: RewardType::TokenFactory,
-        });
+            amount: Uint128::from(10u64),
+            count,
+        }];
+        let funded = mock_info("creator", &coins(10 * u128::from(count), "inj"));
+        let add = ExecuteMsg::AddTokenFactoryReward { box_id: box_id.clone(), rewards };
+        execute(deps.as_mut(), mock_env(), funded, add).unwrap();
+        let publish = ExecuteMsg::PublishBox { box_id, start_time: None };
+        execute(deps.as_mut(), mock_env(), creator, publish).unwrap();
+    }
+
+    #[test]
+    fn test() {
+        let mut weighted_list = Vec::new();
+        let rewards = vec![
+            TokenFactoryReward {
+                id: 1,
+                token_denom: "inj".to_string(),
+                amount: Uint128::from(10000000000000000u64),
+                token_decimals: 18,
+                odds: 10,
+                count: 12,
+                reward_type: RewardType::TokenFactory,
+            },
+            TokenFactoryReward {
+                id: 2,
+                token_denom: "inj".to_string(),
+                amount: Uint128::from(1000000000000000000u64),
+                token_decimals: 18,
+                odds: 10,
+                count: 3,
+                reward_type: RewardType::TokenFactory,
+            }
+        ];
 
         for ticket_info in rewards.clone() {
             for _ in 0..ticket_info.odds {
-                weighted_list.push(ticket_info.id.clone());
+                weighted_list.push(ticket_info.id);
             }
         }
 
         let random_number = 1;
         Uint128::new(random_number as u128);
 
-        let reward_id = weighted_list[random_number as usize].clone();
+        let reward_id = weighted_list[random_number as usize];
         //find the reward with id == winner_id
         let reward = rewards.iter().find(|x| x.id == reward_id);
-        if reward.is_some() {
-            let mut cnt = reward.unwrap().clone();
+        if let Some(reward) = reward {
+            let mut cnt = reward.clone();
             cnt.count -= 1;
         }
-        println!("{:}", reward.unwrap().count)
+    }
+
+    #[test]
+    fn exhausted_rewards_are_never_drawn() {
+        let fbox = fortune_box(OddsModel::Fixed, vec![token_reward(1, 10, 0), token_reward(2, 30, 2)]);
+
+        for seed in 0..100 {
+            assert_eq!(util::draw_outcome(&fbox, &u64::to_be_bytes(seed)), Some(DrawOutcome::Reward(2)));
+        }
+        let (odds, _) = util::outcome_probabilities(&fbox);
+        assert_eq!(odds, vec![(1, Decimal::zero()), (2, Decimal::one())]);
+
+        let empty = fortune_box(OddsModel::Fixed, vec![token_reward(1, 10, 0)]);
+        assert_eq!(util::draw_outcome(&empty, &[7]), None);
+    }
+
+    #[test]
+    fn stock_weighted_odds_follow_remaining_count() {
+        let rewards = vec![token_reward(1, 10, 3), token_reward(2, 10, 1)];
+
+        let (fixed, _) = util::outcome_probabilities(
+            &fortune_box(OddsModel::Fixed, rewards.clone())
+        );
+        assert_eq!(fixed, vec![(1, Decimal::percent(50)), (2, Decimal::percent(50))]);
+
+        let (weighted, _) = util::outcome_probabilities(
+            &fortune_box(OddsModel::StockWeighted, rewards)
+        );
+        assert_eq!(weighted, vec![(1, Decimal::percent(75)), (2, Decimal::percent(25))]);
+    }
+
+    #[test]
+    fn empty_outcome_covers_the_rest_of_max_odds() {
+        let mut fbox = fortune_box(
+            OddsModel::StockWeighted,
+            vec![token_reward(1, 100, 3), token_reward(2, 100, 2)]
+        );
+        fbox.allow_empty = true;
+        fbox.consolation_points = Uint128::from(10u64);
+
+        // 20% of the range wins, split 3:2 by remaining stock
+        let (odds, empty) = util::outcome_probabilities(&fbox);
+        assert_eq!(odds, vec![(1, Decimal::percent(12)), (2, Decimal::percent(8))]);
+        assert_eq!(empty, Decimal::percent(80));
+
+        let mut deps = mock_dependencies();
+        BOX_MAP.save(deps.as_mut().storage, fbox.id.clone(), &fbox).unwrap();
+        let ev = query_expected_value(deps.as_ref(), fbox.id.clone()).unwrap();
+        assert_eq!(ev.win_probability, Decimal::percent(20));
+        assert_eq!(ev.tokens[0].denom, "inj");
+        assert_eq!(ev.tokens[0].amount, Decimal256::from_ratio(200u64, 1u64));
+        assert_eq!(ev.points, Decimal256::from_ratio(8u64, 1u64));
+
+        // Once every reward is gone the box has nothing left to draw
+        fbox.rewards.iter_mut().for_each(|reward| {
+            reward.count = 0;
+        });
+        assert_eq!(util::draw_outcome(&fbox, &[3]), None);
+    }
+
+    #[test]
+    fn rewards_are_checked_against_the_box_max_odds() {
+        let mut deps = setup();
+        let info = mock_info("creator", &coins(1, "inj"));
+
+        let mut msg = create_box_msg("tiny");
+        msg.max_odds = Some(10);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), ExecuteMsg::CreateBox(msg));
+        assert!(matches!(err, Err(ContractError::InvalidMaxOdds { min: 100, max: 1_000_000 })));
+
+        let mut msg = create_box_msg("precise");
+        msg.max_odds = Some(1_000_000);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CreateBox(msg)).unwrap();
+
+        let reward = |odds: u64| TokenFactoryReward {
+            id: 0,
+            odds,
+            token_denom: "inj".to_string(),
+            token_decimals: 18,
+            reward_type: RewardType::TokenFactory,
+            amount: Uint128::from(10u64),
+            count: 1,
+        };
+        let add = |odds: u64| ExecuteMsg::AddTokenFactoryReward {
+            box_id: "precise".to_string(),
+            rewards: vec![reward(odds)],
+        };
+        let info = mock_info("creator", &coins(10, "inj"));
+        // 5000 odds would overflow a default 1000 denominator but fit this box
+        execute(deps.as_mut(), mock_env(), info.clone(), add(500)).unwrap();
+        execute(deps.as_mut(), mock_env(), info.clone(), add(5000)).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info, add(1_000_000));
+        assert!(matches!(err, Err(ContractError::MaxOddsReached { .. })));
+
+        let odds = query_box_odds(deps.as_ref(), "precise".to_string()).unwrap();
+        assert_eq!(odds.max_odds, 1_000_000);
+        assert_eq!(odds.rewards[0].share, Decimal::permille(5) / Uint128::from(10u64));
+    }
+
+    #[test]
+    fn rewards_can_be_edited_until_the_first_open() {
+        let mut deps = setup();
+        let creator = mock_info("creator", &coins(1, "inj"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            creator.clone(),
+            ExecuteMsg::CreateBox(create_box_msg("edit"))
+        ).unwrap();
+        let rewards = vec![
+            TokenFactoryReward {
+                id: 0,
+                odds: 100,
+                token_denom: "inj".to_string(),
+                token_decimals: 18,
+                reward_type: RewardType::TokenFactory,
+                amount: Uint128::from(10u64),
+                count: 2,
+            },
+            TokenFactoryReward {
+                id: 0,
+                odds: 200,
+                token_denom: "inj".to_string(),
+                token_decimals: 18,
+                reward_type: RewardType::TokenFactory,
+                amount: Uint128::from(5u64),
+                count: 1,
+            }
+        ];
+        let msg = ExecuteMsg::AddTokenFactoryReward { box_id: "edit".to_string(), rewards };
+        // Rewards are only added against a deposit of exactly amount times count per denom
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &coins(24, "inj")), msg.clone());
+        assert!(matches!(err, Err(ContractError::AmountNotMatch {})));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &coins(25, "uatom")), msg.clone());
+        assert!(matches!(err, Err(ContractError::PaymentFailed {})));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &coins(25, "inj")), msg).unwrap();
+
+        let update = |odds: u64| ExecuteMsg::UpdateRewardOdds {
+            box_id: "edit".to_string(),
+            reward_id: 1,
+            odds,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("other", &[]), update(300));
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), update(900));
+        assert!(matches!(err, Err(ContractError::MaxOddsReached { .. })));
+        execute(deps.as_mut(), mock_env(), creator.clone(), update(800)).unwrap();
+
+        let top_up = ExecuteMsg::TopUpReward { box_id: "edit".to_string(), reward_id: 1, count: 3 };
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), top_up.clone());
+        assert!(matches!(err, Err(ContractError::AmountNotMatch {})));
+        let info = mock_info("creator", &coins(30, "inj"));
+        execute(deps.as_mut(), mock_env(), info, top_up).unwrap();
+
+        let remove = ExecuteMsg::RemoveReward { box_id: "edit".to_string(), reward_id: 2 };
+        let res = execute(deps.as_mut(), mock_env(), creator.clone(), remove).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(5, "inj"),
+            })
+        );
+
+        let fbox = BOX_MAP.load(deps.as_ref().storage, "edit".to_string()).unwrap();
+        assert_eq!(fbox.rewards.len(), 1);
+        assert_eq!(fbox.rewards[0].odds, 800);
+        assert_eq!(fbox.rewards[0].count, 5);
+
+        let publish = ExecuteMsg::PublishBox { box_id: "edit".to_string(), start_time: None };
+        execute(deps.as_mut(), mock_env(), creator.clone(), publish).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), creator, update(100));
+        assert!(matches!(err, Err(ContractError::BoxLocked {})));
+    }
+
+    #[test]
+    fn box_lifecycle_drives_opens() {
+        let mut deps = setup();
+        let creator = mock_info("creator", &coins(1, "inj"));
+        let mut msg = create_box_msg("season");
+        msg.duration = util::MAX_DURATION + 1;
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(msg.clone()));
+        assert!(matches!(err, Err(ContractError::InvalidDuration { .. })));
+        msg.duration = 100;
+        execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(msg)).unwrap();
+
+        let publish = |start_time: Option<u64>| ExecuteMsg::PublishBox {
+            box_id: "season".to_string(),
+            start_time,
+        };
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), publish(None));
+        assert!(matches!(err, Err(ContractError::EmptyBox {})));
+
+        let rewards = vec![TokenFactoryReward {
+            id: 0,
+            odds: 100,
+            token_denom: "inj".to_string(),
+            token_decimals: 18,
+            reward_type: RewardType::TokenFactory,
+            amount: Uint128::from(10u64),
+            count: 3,
+        }];
+        let add = ExecuteMsg::AddTokenFactoryReward { box_id: "season".to_string(), rewards };
+        let funded = mock_info("creator", &coins(30, "inj"));
+        execute(deps.as_mut(), mock_env(), funded.clone(), add.clone()).unwrap();
+
+        let open = ExecuteMsg::OpenBox { box_id: "season".to_string(), proof: None, referrer: None };
+        let player = mock_info("player", &coins(100, "inj"));
+        let err = execute(deps.as_mut(), mock_env(), player.clone(), open.clone());
+        assert!(matches!(err, Err(ContractError::BoxNotLive { .. })));
+
+        let mut env = mock_env();
+        let err = execute(deps.as_mut(), env.clone(), creator.clone(), publish(Some(u64::MAX)));
+        assert!(matches!(err, Err(ContractError::InvalidStartTime {})));
+        let start = env.block.time.seconds() + 50;
+        execute(deps.as_mut(), env.clone(), creator.clone(), publish(Some(start))).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), funded, add);
+        assert!(matches!(err, Err(ContractError::BoxLocked {})));
+        let err = execute(deps.as_mut(), env.clone(), player.clone(), open.clone());
+        assert!(matches!(err, Err(ContractError::BoxNotLive { .. })));
+
+        env.block.time = env.block.time.plus_seconds(50);
+        execute(deps.as_mut(), env.clone(), player.clone(), open.clone()).unwrap();
+        let live = query_boxes(deps.as_ref(), env.clone(), Some(BoxStatus::Live)).unwrap();
+        assert_eq!(live.boxes.len(), 1);
+
+        env.block.time = env.block.time.plus_seconds(100);
+        let err = execute(deps.as_mut(), env.clone(), player, open);
+        assert!(matches!(err, Err(ContractError::BoxTerminated {})));
+        let expired = query_boxes(deps.as_ref(), env.clone(), Some(BoxStatus::Expired)).unwrap();
+        assert_eq!(expired.boxes.len(), 1);
+
+        let cancel = ExecuteMsg::CancelBox { box_id: "season".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("player", &[]), cancel.clone());
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        let res = execute(deps.as_mut(), env.clone(), creator.clone(), cancel.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(20, "inj"),
+            })
+        );
+        let err = execute(deps.as_mut(), env, creator, cancel);
+        assert!(matches!(err, Err(ContractError::InvalidTransition { .. })));
+    }
+
+    #[test]
+    fn paused_boxes_reject_opens_until_resumed() {
+        let mut deps = setup();
+        let creator = mock_info("creator", &[]);
+        publish_box(&mut deps, create_box_msg("pause"), 5);
+
+        let mut env = mock_env();
+        let pause = |resume_at: Option<u64>| ExecuteMsg::PauseBox {
+            box_id: "pause".to_string(),
+            resume_at,
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("player", &[]), pause(None));
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        let res = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), pause(None)).unwrap();
+        assert_eq!(res.events[0].ty, "box_paused");
+
+        let open = ExecuteMsg::OpenBox { box_id: "pause".to_string(), proof: None, referrer: None };
+        let player = mock_info("player", &coins(100, "inj"));
+        let err = execute(deps.as_mut(), env.clone(), player.clone(), open.clone());
+        assert!(matches!(err, Err(ContractError::BoxPaused {})));
+
+        let resume = ExecuteMsg::ResumeBox { box_id: "pause".to_string() };
+        let res = execute(deps.as_mut(), env.clone(), creator.clone(), resume).unwrap();
+        assert_eq!(res.events[0].ty, "box_resumed");
+        execute(deps.as_mut(), env.clone(), player.clone(), open.clone()).unwrap();
+
+        let now = env.block.time.seconds();
+        let err = execute(deps.as_mut(), env.clone(), creator.clone(), pause(Some(now)));
+        assert!(matches!(err, Err(ContractError::InvalidResumeTime {})));
+        let resume_at = now + 60;
+        execute(deps.as_mut(), env.clone(), creator, pause(Some(resume_at))).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), player.clone(), open.clone());
+        assert!(matches!(err, Err(ContractError::BoxPaused {})));
+        env.block.time = env.block.time.plus_seconds(60);
+        execute(deps.as_mut(), env, player, open).unwrap();
+    }
+
+    #[test]
+    fn featured_slots_are_limited_and_expire() {
+        let mut deps = setup();
+        let owner = mock_info("owner", &[]);
+        let msg = ExecuteMsg::UpdateFeatureConfig {
+            feature_fees: Uint128::from(10u64),
+            feature_period: 100,
+            max_featured: 1,
+        };
+        execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+        for box_id in ["first", "second"] {
+            let msg = ExecuteMsg::CreateBox(create_box_msg(box_id));
+            execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        }
+
+        let feature = |box_id: &str, slots: u64| ExecuteMsg::FeatureBox {
+            box_id: box_id.to_string(),
+            slots,
+        };
+        let mut env = mock_env();
+        let now = env.block.time.seconds();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &coins(10, "inj")),
+            feature("first", 2)
+        );
+        assert!(matches!(err, Err(ContractError::AmountNotMatch {})));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &coins(10, "inj")),
+            feature("first", u64::MAX)
+        );
+        assert!(matches!(err, Err(ContractError::InvalidSlots {})));
+        let slots = u64::MAX / 100;
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &coins(10 * u128::from(slots), "inj")),
+            feature("first", slots)
+        );
+        assert!(matches!(err, Err(ContractError::InvalidSlots {})));
+
+        let paid = mock_info("creator", &coins(20, "inj"));
+        let res = execute(deps.as_mut(), env.clone(), paid.clone(), feature("first", 2)).unwrap();
+        assert!(res.messages.is_empty());
+        let treasury = query_treasury(deps.as_ref()).unwrap();
+        assert_eq!(treasury.balances[0].available, Uint128::from(20u64));
+        let err = execute(deps.as_mut(), env.clone(), paid.clone(), feature("second", 2));
+        assert!(matches!(err, Err(ContractError::FeaturedSlotsFull {})));
+        // Extending an existing listing does not need a new slot
+        execute(deps.as_mut(), env.clone(), paid.clone(), feature("first", 2)).unwrap();
+
+        let featured = query_featured(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(featured.boxes.len(), 1);
+        assert_eq!(featured.boxes[0].featured_until, now + 400);
+
+        env.block.time = env.block.time.plus_seconds(400);
+        execute(deps.as_mut(), env.clone(), paid, feature("second", 2)).unwrap();
+        let featured = query_featured(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(featured.boxes[0].box_id, "second");
+
+        // Closing a box gives its slot back right away
+        let cancel = ExecuteMsg::CancelBox { box_id: "second".to_string() };
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), cancel).unwrap();
+        assert!(query_featured(deps.as_ref(), env).unwrap().boxes.is_empty());
+    }
+
+    #[test]
+    fn feature_auction_refunds_outbid_creators() {
+        let mut deps = setup();
+        for (creator, box_id) in [("alice", "box-a"), ("bob", "box-b")] {
+            let msg = ExecuteMsg::CreateBox(create_box_msg(box_id));
+            execute(deps.as_mut(), mock_env(), mock_info(creator, &[]), msg).unwrap();
+        }
+        let open = ExecuteMsg::OpenFeatureAuction {
+            slots: 1,
+            min_bid: Uint128::from(10u64),
+            duration: 100,
+            feature_duration: 1000,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), open.clone());
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        let endless = [
+            ExecuteMsg::OpenFeatureAuction {
+                slots: 1,
+                min_bid: Uint128::from(10u64),
+                duration: u64::MAX,
+                feature_duration: 1000,
+            },
+            ExecuteMsg::OpenFeatureAuction {
+                slots: 1,
+                min_bid: Uint128::from(10u64),
+                duration: 100,
+                feature_duration: u64::MAX,
+            },
+        ];
+        for msg in endless {
+            let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg);
+            assert!(matches!(err, Err(ContractError::InvalidDuration { .. })));
+        }
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), open.clone()).unwrap();
+
+        let bid = |box_id: &str| ExecuteMsg::BidFeatureAuction {
+            auction_id: 1,
+            box_id: box_id.to_string(),
+        };
+        let mut env = mock_env();
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "inj")), bid("box-a"));
+        assert!(matches!(err, Err(ContractError::BidTooLow { .. })));
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(10, "inj")), bid("box-a")).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(10, "inj")), bid("box-b"));
+        assert!(matches!(err, Err(ContractError::BidTooLow { min }) if min == Uint128::from(11u64)));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &coins(15, "inj")),
+            bid("box-b")
+        ).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(10, "inj") })
+        );
+
+        let bids = query_auction_bids(deps.as_ref(), 1).unwrap();
+        assert_eq!(bids.bids.len(), 1);
+        assert_eq!(bids.bids[0].box_id, "box-b");
+
+        let settle = ExecuteMsg::SettleFeatureAuction { auction_id: 1 };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), settle.clone());
+        assert!(matches!(err, Err(ContractError::AuctionRunning {})));
+        env.block.time = env.block.time.plus_seconds(100);
+        execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), settle).unwrap();
+        let treasury = query_treasury(deps.as_ref()).unwrap();
+        assert_eq!(treasury.balances[0].collected, Uint128::from(15u64));
+
+        let featured = query_featured(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(featured.boxes.len(), 1);
+        assert_eq!(featured.boxes[0].box_id, "box-b");
+        assert_eq!(featured.boxes[0].featured_until, env.block.time.seconds() + 1000);
+
+        // A won auction still cannot push the featured list past its cap
+        let config = ExecuteMsg::UpdateFeatureConfig {
+            feature_fees: Uint128::from(10u64),
+            feature_period: 100,
+            max_featured: 1,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), config).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), open).unwrap();
+        let bid = ExecuteMsg::BidFeatureAuction { auction_id: 2, box_id: "box-a".to_string() };
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(10, "inj")), bid).unwrap();
+        env.block.time = env.block.time.plus_seconds(100);
+        let settle = ExecuteMsg::SettleFeatureAuction { auction_id: 2 };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), settle).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(10, "inj") })
+        );
+        let featured = query_featured(deps.as_ref(), env).unwrap();
+        assert_eq!(featured.boxes.len(), 1);
+    }
+
+    #[test]
+    fn treasury_withdrawals_never_touch_escrow() {
+        // 50 inj escrowed as rewards plus 5 inj of dev cut from a 100 inj open
+        let mut deps = setup_with_balance(&coins(55, "inj"));
+        publish_box(&mut deps, create_box_msg("vault"), 6);
+        let open = ExecuteMsg::OpenBox { box_id: "vault".to_string(), proof: None, referrer: None };
+        execute(deps.as_mut(), mock_env(), mock_info("player", &coins(100, "inj")), open).unwrap();
+
+        let treasury = query_treasury(deps.as_ref()).unwrap();
+        assert_eq!(treasury.balances[0].available, Uint128::from(5u64));
+
+        let withdraw = |amount: u64| ExecuteMsg::WithdrawTreasury {
+            asset: AssetInfo::Native { denom: "inj".to_string() },
+            amount: Uint128::from(amount),
+            recipient: "dev".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("dev", &[]), withdraw(5));
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        let owner = mock_info("owner", &[]);
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), withdraw(6));
+        assert!(matches!(err, Err(ContractError::InsufficientTreasury {})));
+
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), withdraw(5)).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "dev".to_string(), amount: coins(5, "inj") })
+        );
+
+        // A ledger that disagrees with the real balance still cannot dip into escrow
+        deps.querier.update_balance(mock_env().contract.address, coins(50, "inj"));
+        crate::util::accrue_fee(deps.as_mut().storage, "inj".to_string(), Uint128::from(5u64)).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), owner, withdraw(5));
+        assert!(matches!(err, Err(ContractError::EscrowViolation {})));
+
+        // Escrow follows deposits and payouts, stock handed back on cancel leaves it
+        let inj = AssetInfo::Native { denom: "inj".to_string() };
+        assert_eq!(crate::util::escrowed_amount(deps.as_ref(), &inj).unwrap(), Uint128::from(50u64));
+        let cancel = ExecuteMsg::CancelBox { box_id: "vault".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), cancel).unwrap();
+        assert_eq!(crate::util::escrowed_amount(deps.as_ref(), &inj).unwrap(), Uint128::zero());
+    }
+
+    #[test]
+    fn only_registered_assets_are_accepted() {
+        let mut deps = setup();
+        let usdt = AssetInfo::Native { denom: "factory/usdt".to_string() };
+
+        let mut msg = create_box_msg("usdt");
+        msg.price.info = usdt.clone();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::CreateBox(msg.clone())
+        ).unwrap_err();
+        assert!(matches!(err, ContractError::AssetNotAllowed { .. }));
+
+        let register = ExecuteMsg::RegisterAsset { info: usdt.clone(), decimals: Some(6) };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            register.clone()
+        ).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::RegisterAsset { info: usdt.clone(), decimals: None }
+        ).unwrap_err();
+        assert!(matches!(err, ContractError::MissingDecimals {}));
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), register).unwrap();
+
+        let assets = query_allowed_assets(deps.as_ref()).unwrap().assets;
+        assert_eq!(assets.len(), 2);
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::CreateBox(msg)
+        ).unwrap();
+        let fbox = BOX_MAP.load(deps.as_ref().storage, "usdt".to_string()).unwrap();
+        assert_eq!(fbox.price.info, usdt);
+        assert_eq!(fbox.price_decimals, 6);
+
+        // Rewards have to use a registered denom with its registered decimals
+        let reward = TokenFactoryReward {
+            id: 1,
+            odds: 10,
+            token_denom: "factory/usdt".to_string(),
+            token_decimals: 18,
+            reward_type: RewardType::TokenFactory,
+            amount: Uint128::from(10u64),
+            count: 1,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &coins(10, "inj")),
+            ExecuteMsg::AddTokenFactoryReward { box_id: "usdt".to_string(), rewards: vec![reward] }
+        ).unwrap_err();
+        assert!(matches!(err, ContractError::DecimalsMismatch { expected: 6 }));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::RemoveAsset { info: usdt }
+        ).unwrap();
+        let mut msg = create_box_msg("other");
+        msg.price.info = AssetInfo::Native { denom: "factory/usdt".to_string() };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::CreateBox(msg)
+        ).unwrap_err();
+        assert!(matches!(err, ContractError::AssetNotAllowed { .. }));
+    }
+
+    #[test]
+    fn assets_round_trip_through_json() {
+        let native = Asset {
+            info: AssetInfo::Native { denom: "inj".to_string() },
+            amount: Uint128::from(5u64),
+        };
+        let json = to_json_binary(&native).unwrap();
+        assert_eq!(
+            String::from_utf8(json.to_vec()).unwrap(),
+            r#"{"info":{"native":{"denom":"inj"}},"amount":"5"}"#
+        );
+        assert_eq!(from_json::<Asset>(&json).unwrap(), native);
+
+        let cw20 = AssetInfo::Cw20 { contract_addr: Addr::unchecked("usdc") };
+        let json = to_json_binary(&cw20).unwrap();
+        assert_eq!(String::from_utf8(json.to_vec()).unwrap(), r#"{"cw20":{"contract_addr":"usdc"}}"#);
+        assert_eq!(from_json::<AssetInfo>(&json).unwrap(), cw20);
+
+        let reward = token_reward(1, 10, 2);
+        let json = to_json_binary(&reward).unwrap();
+        assert_eq!(from_json::<RewardData>(&json).unwrap(), reward);
+    }
+
+    #[test]
+    fn cw20_priced_boxes_are_opened_through_the_token() {
+        let mut deps = setup();
+        let usdc = AssetInfo::Cw20 { contract_addr: Addr::unchecked("usdc") };
+        ALLOWED_ASSETS.save(
+            deps.as_mut().storage,
+            usdc.key(),
+            &(AllowedAsset { info: usdc.clone(), decimals: 6 })
+        ).unwrap();
+
+        let mut msg = create_box_msg("stable");
+        msg.price = Asset { info: usdc.clone(), amount: Uint128::from(100u64) };
+        publish_box(&mut deps, msg, 1);
+
+        // Native funds can not pay for a cw20 priced box
+        let open = ExecuteMsg::OpenBox { box_id: "stable".to_string(), proof: None, referrer: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("player", &coins(100, "inj")), open);
+        assert!(matches!(err, Err(ContractError::PaymentFailed {})));
+
+        let hook = |amount: u64| {
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "player".to_string(),
+                amount: Uint128::from(amount),
+                msg: to_json_binary(
+                    &(TokenReceiveMsg::OpenBox { box_id: "stable".to_string(), proof: None, referrer: None })
+                ).unwrap(),
+            })
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("usdc", &[]), hook(99));
+        assert!(matches!(err, Err(ContractError::AmountNotMatch {})));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("fake", &[]), hook(100));
+        assert!(matches!(err, Err(ContractError::PaymentFailed {})));
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("usdc", &[]), hook(100)).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(10, "inj"),
+            })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "usdc".to_string(),
+                msg: to_json_binary(
+                    &(Cw20ExecuteMsg::Transfer {
+                        recipient: "creator".to_string(),
+                        amount: Uint128::from(95u64),
+                    })
+                ).unwrap(),
+                funds: vec![],
+            })
+        );
+        let treasury = query_treasury(deps.as_ref()).unwrap().balances;
+        assert_eq!(treasury[0].denom, "usdc");
+        assert_eq!(treasury[0].collected, Uint128::from(5u64));
+    }
+
+    #[test]
+    fn malformed_addresses_are_rejected() {
+        let instantiate_msg = |owner: &str, dev: &str| InstantiateMsg {
+            owner: owner.to_string(),
+            native_token: "inj".to_string(),
+            founder_addr: "founder".to_string(),
+            dev_addr: dev.to_string(),
+        };
+        let mut deps = mock_dependencies();
+        let info = mock_info("owner", &[]);
+        let err = instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg("Owner", "dev"));
+        assert!(matches!(err, Err(ContractError::InvalidAddress { field, .. }) if field == "owner"));
+        let err = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg("owner", "d"));
+        assert!(matches!(err, Err(ContractError::InvalidAddress { field, .. }) if field == "dev"));
+
+        let mut deps = setup();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::CreateBox(create_box_msg("box"))
+        ).unwrap();
+
+        let nft = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "creator".to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(
+                &(NftReceiveMsg::AddNftReward {
+                    box_id: "box".to_string(),
+                    collection_addr: "Punks".to_string(),
+                    nft_id: "1".to_string(),
+                    odds: 10,
+                })
+            ).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("punks", &[]), nft);
+        assert!(matches!(err, Err(ContractError::InvalidAddress { field, .. }) if field == "collection"));
+
+        ALLOWED_ASSETS.save(
+            deps.as_mut().storage,
+            "usdc".to_string(),
+            &(AllowedAsset {
+                info: AssetInfo::Cw20 { contract_addr: Addr::unchecked("usdc") },
+                decimals: 6,
+            })
+        ).unwrap();
+        let coin = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "creator".to_string(),
+            amount: Uint128::from(10u64),
+            msg: to_json_binary(
+                &(TokenReceiveMsg::CreateFortuneBoxWithCoin {
+                    box_id: "coin".to_string(),
+                    creator: "CREATOR".to_string(),
+                    denom: "usdc".to_string(),
+                    amount: Uint128::from(10u64),
+                    odds: 10,
+                    price: create_box_msg("coin").price,
+                    duration: 0,
+                })
+            ).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("usdc", &[]), coin);
+        assert!(matches!(err, Err(ContractError::InvalidAddress { field, .. }) if field == "creator"));
+    }
+
+    #[test]
+    fn box_ids_are_validated_or_issued() {
+        let mut deps = setup();
+        let creator = mock_info("creator", &[]);
+        for box_id in ["", "ab", "Box", "box id", "12345", &"x".repeat(33)] {
+            let mut msg = create_box_msg("box");
+            msg.box_id = Some(box_id.to_string());
+            let err = execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(msg));
+            assert!(matches!(err, Err(ContractError::InvalidBoxId { .. })), "{}", box_id);
+        }
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            creator.clone(),
+            ExecuteMsg::CreateBox(create_box_msg("my-box_2"))
+        ).unwrap();
+
+        for expected in ["1", "2"] {
+            let mut msg = create_box_msg("box");
+            msg.box_id = None;
+            let res = execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(msg));
+            let data: CreateBoxResponse = from_json(res.unwrap().data.unwrap()).unwrap();
+            assert_eq!(data.box_id, expected);
+            assert!(BOX_MAP.has(deps.as_ref().storage, expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn allowlisted_boxes_need_a_proof() {
+        let leaves = vec![
+            merkle::leaf_hash("alice", Some(1)),
+            merkle::leaf_hash("bob", None),
+            merkle::leaf_hash("carol", Some(2))
+        ];
+        let tree = merkle::MerkleTree::new(leaves.clone());
+        let root = tree.root().unwrap();
+        for (index, leaf) in leaves.iter().enumerate() {
+            assert!(merkle::verify(&root, *leaf, &tree.proof(index)));
+        }
+        let proof = |index: usize, limit: Option<u64>| {
+            Some(AllowlistProof {
+                limit,
+                proof: tree
+                    .proof(index)
+                    .iter()
+                    .map(|node| HexBinary::from(node.to_vec()))
+                    .collect(),
+            })
+        };
+
+        let mut deps = setup();
+        let creator = mock_info("creator", &coins(30, "inj"));
+        let mut msg = create_box_msg("private");
+        msg.allowlist_root = Some(HexBinary::from(vec![1u8; 20]));
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(msg.clone()));
+        assert!(matches!(err, Err(ContractError::InvalidMerkleRoot {})));
+        msg.allowlist_root = Some(HexBinary::from(root.to_vec()));
+        publish_box(&mut deps, msg, 3);
+
+        let open = |proof: Option<AllowlistProof>| ExecuteMsg::OpenBox {
+            box_id: "private".to_string(),
+            proof,
+            referrer: None,
+        };
+        let paid = |sender: &str| mock_info(sender, &coins(100, "inj"));
+
+        let err = execute(deps.as_mut(), mock_env(), paid("alice"), open(None));
+        assert!(matches!(err, Err(ContractError::NotAllowlisted {})));
+        // A proof only works for the address and limit it was built for
+        let err = execute(deps.as_mut(), mock_env(), paid("dave"), open(proof(1, None)));
+        assert!(matches!(err, Err(ContractError::NotAllowlisted {})));
+        let err = execute(deps.as_mut(), mock_env(), paid("alice"), open(proof(0, Some(5))));
+        assert!(matches!(err, Err(ContractError::NotAllowlisted {})));
+
+        execute(deps.as_mut(), mock_env(), paid("alice"), open(proof(0, Some(1)))).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), paid("alice"), open(proof(0, Some(1))));
+        assert!(matches!(err, Err(ContractError::OpenLimitReached {})));
+        execute(deps.as_mut(), mock_env(), paid("bob"), open(proof(1, None))).unwrap();
+
+        // Publishing locks the allowlist with the rest of the configuration
+        let clear = ExecuteMsg::SetAllowlist { box_id: "private".to_string(), root: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), clear.clone());
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), clear);
+        assert!(matches!(err, Err(ContractError::BoxLocked {})));
+        let err = execute(deps.as_mut(), mock_env(), paid("dave"), open(None));
+        assert!(matches!(err, Err(ContractError::NotAllowlisted {})));
+
+        // Drafts can still change or clear it
+        execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(create_box_msg("draft"))).unwrap();
+        let set = ExecuteMsg::SetAllowlist { box_id: "draft".to_string(), root: Some(HexBinary::from(root.to_vec())) };
+        execute(deps.as_mut(), mock_env(), creator.clone(), set).unwrap();
+        let clear = ExecuteMsg::SetAllowlist { box_id: "draft".to_string(), root: None };
+        execute(deps.as_mut(), mock_env(), creator, clear).unwrap();
+        assert_eq!(BOX_MAP.load(deps.as_ref().storage, "draft".to_string()).unwrap().allowlist_root, None);
+    }
+
+    #[test]
+    fn holder_gated_boxes_check_nfts_and_balances() {
+        let mut deps = setup();
+        deps.querier.update_wasm(|query| {
+            let (contract_addr, msg) = match query {
+                WasmQuery::Smart { contract_addr, msg } => (contract_addr.as_str(), msg),
+                _ => panic!("unexpected query"),
+            };
+            let res = match contract_addr {
+                "punks" => {
+                    let tokens = match from_json(msg).unwrap() {
+                        Cw721QueryMsg::Tokens { owner, .. } if owner == "alice" => {
+                            vec!["1".to_string()]
+                        }
+                        _ => vec![],
+                    };
+                    to_json_binary(&(TokensResponse { tokens }))
+                }
+                "usdc" => {
+                    let balance = match from_json(msg).unwrap() {
+                        Cw20QueryMsg::Balance { address } if address == "bob" => 500u64,
+                        _ => 50u64,
+                    };
+                    to_json_binary(&(Cw20BalanceResponse { balance: Uint128::from(balance) }))
+                }
+                _ => panic!("unexpected contract"),
+            };
+            SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+        });
+
+        let creator = mock_info("creator", &coins(30, "inj"));
+        let mut msg = create_box_msg("holders");
+        msg.holder_gate = Some(vec![HolderRequirement::Nft { collection: "Punks".to_string() }]);
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(msg.clone()));
+        assert!(matches!(err, Err(ContractError::InvalidAddress { .. })));
+        msg.holder_gate = Some(vec![
+            HolderRequirement::Nft { collection: "punks".to_string() },
+            HolderRequirement::Balance {
+                asset: Asset {
+                    info: AssetInfo::Cw20 { contract_addr: Addr::unchecked("usdc") },
+                    amount: Uint128::from(100u64),
+                },
+            }
+        ]);
+        publish_box(&mut deps, msg, 3);
+
+        let open = ExecuteMsg::OpenBox { box_id: "holders".to_string(), proof: None, referrer: None };
+        let paid = |sender: &str| mock_info(sender, &coins(100, "inj"));
+        let err = execute(deps.as_mut(), mock_env(), paid("carol"), open.clone());
+        assert!(matches!(err, Err(ContractError::NotEligible {})));
+        execute(deps.as_mut(), mock_env(), paid("alice"), open.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), paid("bob"), open).unwrap();
+    }
+
+    #[test]
+    fn open_limits_cap_addresses_days_and_boxes() {
+        let mut deps = setup();
+        let mut msg = create_box_msg("capped");
+        msg.open_limits = Some(OpenLimits {
+            per_address: Some(2),
+            per_address_daily: Some(1),
+            total: Some(3),
+        });
+        publish_box(&mut deps, msg, 10);
+
+        let open = ExecuteMsg::OpenBox { box_id: "capped".to_string(), proof: None, referrer: None };
+        let paid = |sender: &str| mock_info(sender, &coins(100, "inj"));
+        let allowance = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, env| {
+            query_open_allowance(deps.as_ref(), env, "capped".to_string(), "alice".to_string())
+                .unwrap()
+        };
+        let mut env = mock_env();
+
+        assert_eq!(allowance(&deps, env.clone()).remaining, Some(1));
+        execute(deps.as_mut(), env.clone(), paid("alice"), open.clone()).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), paid("alice"), open.clone());
+        assert!(matches!(err, Err(ContractError::DailyLimitReached {})));
+        let left = allowance(&deps, env.clone());
+        assert_eq!((left.remaining_for_address, left.remaining_today), (Some(1), Some(0)));
+
+        env.block.time = env.block.time.plus_seconds(86400);
+        execute(deps.as_mut(), env.clone(), paid("alice"), open.clone()).unwrap();
+        env.block.time = env.block.time.plus_seconds(86400);
+        let err = execute(deps.as_mut(), env.clone(), paid("alice"), open.clone());
+        assert!(matches!(err, Err(ContractError::OpenLimitReached {})));
+        assert_eq!(allowance(&deps, env.clone()).remaining, Some(0));
+
+        // The third open hits the box cap and sells it out with stock left
+        let res = execute(deps.as_mut(), env.clone(), paid("bob"), open.clone()).unwrap();
+        assert_eq!(
+            res.messages.last().unwrap().msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(70, "inj"),
+            })
+        );
+        let err = execute(deps.as_mut(), env.clone(), paid("carol"), open);
+        assert!(matches!(err, Err(ContractError::BoxTerminated {})));
+        let fbox = BOX_MAP.load(deps.as_ref().storage, "capped".to_string()).unwrap();
+        assert!(fbox.rewards.iter().all(|reward| reward.count == 0));
+
+        // With the leftovers returned only the fees of the three opens are held
+        deps.querier.update_balance(mock_env().contract.address, coins(15, "inj"));
+        let withdraw = ExecuteMsg::WithdrawTreasury {
+            asset: AssetInfo::Native { denom: "inj".to_string() },
+            amount: Uint128::from(15u64),
+            recipient: "dev".to_string(),
+        };
+        execute(deps.as_mut(), env, mock_info("owner", &[]), withdraw).unwrap();
+    }
+
+    #[test]
+    fn nft_keys_are_burned_on_open() {
+        let mut deps = setup();
+        let mut msg = create_box_msg("keyed");
+        msg.key = Some(BoxKey::Nft { collection: "keys".to_string(), burn: true });
+        publish_box(&mut deps, msg, 3);
+
+        let open = ExecuteMsg::OpenBox { box_id: "keyed".to_string(), proof: None, referrer: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(100, "inj")), open);
+        assert!(matches!(err, Err(ContractError::KeyRequired {})));
+
+        let key = |token_id: &str| {
+            ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+                sender: "alice".to_string(),
+                token_id: token_id.to_string(),
+                msg: to_json_binary(
+                    &(NftReceiveMsg::OpenBox { box_id: "keyed".to_string(), proof: None })
+                ).unwrap(),
+            })
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("punks", &[]), key("7"));
+        assert!(matches!(err, Err(ContractError::InvalidKey {})));
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("keys", &[]), key("7")).unwrap();
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "keys".to_string(),
+                msg: Binary::from(br#"{"burn":{"token_id":"7"}}"#.to_vec()),
+                funds: vec![],
+            })
+        );
+
+        let history = query_open_history(deps.as_ref(), "keyed".to_string(), None, None).unwrap();
+        assert_eq!(history.opens.len(), 1);
+        assert_eq!(history.opens[0].opener, Addr::unchecked("alice"));
+        assert_eq!(history.opens[0].payment, None);
+        assert_eq!(
+            history.opens[0].key,
+            Some(KeyUsed::Nft { collection: "keys".to_string(), token_id: "7".to_string() })
+        );
+    }
+
+    #[test]
+    fn held_cw20_keys_go_to_the_creator_on_close() {
+        let mut deps = setup();
+        let mut msg = create_box_msg("tokens");
+        msg.key = Some(BoxKey::Cw20 {
+            token: "ticket".to_string(),
+            amount: Uint128::from(5u64),
+            burn: false,
+        });
+        publish_box(&mut deps, msg, 3);
+
+        let key = |amount: u64| {
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "alice".to_string(),
+                amount: Uint128::from(amount),
+                msg: to_json_binary(
+                    &(TokenReceiveMsg::OpenBoxWithKey { box_id: "tokens".to_string(), proof: None })
+                ).unwrap(),
+            })
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("ticket", &[]), key(4));
+        assert!(matches!(err, Err(ContractError::InvalidKey {})));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("ticket", &[]), key(5)).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        execute(deps.as_mut(), mock_env(), mock_info("ticket", &[]), key(5)).unwrap();
+
+        let fbox = BOX_MAP.load(deps.as_ref().storage, "tokens".to_string()).unwrap();
+        assert_eq!(fbox.held_key_amount, Uint128::from(10u64));
+        let history = query_open_history(deps.as_ref(), "tokens".to_string(), Some(0), None).unwrap();
+        assert_eq!(history.opens.len(), 1);
+        assert_eq!(history.opens[0].index, 1);
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::CancelBox { box_id: "tokens".to_string() }
+        ).unwrap();
+        assert_eq!(
+            res.messages.last().unwrap().msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "ticket".to_string(),
+                msg: to_json_binary(
+                    &(Cw20ExecuteMsg::Transfer {
+                        recipient: "creator".to_string(),
+                        amount: Uint128::from(10u64),
+                    })
+                ).unwrap(),
+                funds: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn credits_are_spent_before_payment() {
+        let mut deps = setup();
+        publish_box(&mut deps, create_box_msg("promo"), 10);
+        publish_box(&mut deps, create_box_msg("other"), 10);
+        let now = mock_env().block.time.seconds();
+
+        let issue = |scope: CreditScope, count: u64, expires_at: Option<u64>| ExecuteMsg::IssueCredits {
+            scope,
+            recipients: vec![CreditRecipient { address: "alice".to_string(), count }],
+            expires_at,
+        };
+        let box_scope = CreditScope::Box { box_id: "promo".to_string() };
+        let creator_scope = CreditScope::Creator { creator: "creator".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), issue(box_scope.clone(), 1, None));
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), issue(box_scope.clone(), 0, None));
+        assert!(matches!(err, Err(ContractError::InvalidCreditCount {})));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), issue(box_scope.clone(), 1, None)).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), issue(box_scope.clone(), u64::MAX, None));
+        assert!(matches!(err, Err(ContractError::InvalidCreditCount {})));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            issue(creator_scope.clone(), 2, Some(now + 100))
+        ).unwrap();
+        assert_eq!(query_credits(deps.as_ref(), mock_env(), "alice".to_string()).unwrap().credits.len(), 2);
+
+        // Box credits go first, any funds sent along are refunded
+        let open = |box_id: &str| ExecuteMsg::OpenBox { box_id: box_id.to_string(), proof: None, referrer: None };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(100, "inj")), open("promo")).unwrap();
+        assert!(
+            res.messages.iter().any(|msg| {
+                msg.msg ==
+                    CosmosMsg::Bank(BankMsg::Send {
+                        to_address: "alice".to_string(),
+                        amount: coins(100, "inj"),
+                    })
+            })
+        );
+        assert!(!res.messages.iter().any(|msg| matches!(&msg.msg, CosmosMsg::Bank(BankMsg::Send { to_address, .. }) if to_address == "creator")));
+        let history = query_open_history(deps.as_ref(), "promo".to_string(), None, None).unwrap();
+        assert_eq!(history.opens[0].credit, Some(box_scope));
+        assert_eq!(history.opens[0].payment, None);
+
+        // Creator credits cover any of the creator's boxes until they expire
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), open("other")).unwrap();
+        let credits = query_credits(deps.as_ref(), mock_env(), "alice".to_string()).unwrap().credits;
+        assert_eq!(credits, vec![CreditGrant { scope: creator_scope, count: 1, expires_at: Some(now + 100) }]);
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        assert!(query_credits(deps.as_ref(), env.clone(), "alice".to_string()).unwrap().credits.is_empty());
+        let err = execute(deps.as_mut(), env, mock_info("alice", &[]), open("other"));
+        assert!(matches!(err, Err(ContractError::PaymentFailed {})));
+    }
+
+    #[test]
+    fn credit_drops_are_claimed_once() {
+        let mut deps = setup();
+        publish_box(&mut deps, create_box_msg("promo"), 10);
+        let leaves = vec![merkle::leaf_hash("alice", Some(2)), merkle::leaf_hash("bob", Some(1))];
+        let tree = merkle::MerkleTree::new(leaves);
+        let proof = |index: usize| -> Vec<HexBinary> {
+            tree.proof(index)
+                .iter()
+                .map(|node| HexBinary::from(node.to_vec()))
+                .collect()
+        };
+        let drop = ExecuteMsg::CreateCreditDrop {
+            scope: CreditScope::Box { box_id: "promo".to_string() },
+            root: HexBinary::from(tree.root().unwrap().to_vec()),
+            expires_at: Some(mock_env().block.time.seconds() + 100),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), drop).unwrap();
+
+        let claim = |count: u64, proof: Vec<HexBinary>| ExecuteMsg::ClaimCredits { drop_id: 1, count, proof };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), claim(3, proof(0)));
+        assert!(matches!(err, Err(ContractError::InvalidProof {})));
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), claim(2, proof(0))).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), claim(2, proof(0)));
+        assert!(matches!(err, Err(ContractError::AlreadyClaimed {})));
+        let credits = query_credits(deps.as_ref(), mock_env(), "alice".to_string()).unwrap().credits;
+        assert_eq!(credits[0].count, 2);
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let err = execute(deps.as_mut(), env, mock_info("bob", &[]), claim(1, proof(1)));
+        assert!(matches!(err, Err(ContractError::DropExpired {})));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            ExecuteMsg::ClaimCredits { drop_id: 2, count: 1, proof: proof(1) }
+        );
+        assert!(matches!(err, Err(ContractError::DropNotFound {})));
+    }
+
+    #[test]
+    fn referrers_earn_a_share_of_the_fee() {
+        let mut deps = setup();
+        publish_box(&mut deps, create_box_msg("shared"), 10);
+        let owner = mock_info("owner", &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            owner.clone(),
+            ExecuteMsg::UpdateReferralShare { referral_bps: 10_001 }
+        );
+        assert!(matches!(err, Err(ContractError::InvalidReferralShare { max: 10_000 })));
+        execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::UpdateReferralShare { referral_bps: 4000 }).unwrap();
+
+        let open = |referrer: &str| ExecuteMsg::OpenBox {
+            box_id: "shared".to_string(),
+            proof: None,
+            referrer: Some(referrer.to_string()),
+        };
+        let paid = |sender: &str| mock_info(sender, &coins(100, "inj"));
+        let err = execute(deps.as_mut(), mock_env(), paid("alice"), open("alice"));
+        assert!(matches!(err, Err(ContractError::SelfReferral {})));
+        execute(deps.as_mut(), mock_env(), paid("alice"), open("bob")).unwrap();
+        execute(deps.as_mut(), mock_env(), paid("carol"), open("bob")).unwrap();
+        execute(deps.as_mut(), mock_env(), paid("alice"), open("dave")).unwrap();
+
+        // 40% of the 5 inj fee goes to the referrer, the rest stays with the protocol
+        let treasury = query_treasury(deps.as_ref()).unwrap();
+        assert_eq!(treasury.balances[0].collected, Uint128::from(9u64));
+        let stats = query_referral_stats(deps.as_ref(), "bob".to_string()).unwrap();
+        assert_eq!(stats.stats.referrals, 2);
+        assert_eq!(stats.claimable, vec![Asset::from(Coin::new(4, "inj"))]);
+        let leaderboard = query_referral_leaderboard(deps.as_ref(), None, None).unwrap();
+        let ranked: Vec<_> = leaderboard.referrers.iter().map(|x| x.address.as_str()).collect();
+        assert_eq!(ranked, vec!["bob", "dave"]);
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), ExecuteMsg::ClaimReferralRewards {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(4, "inj") })
+        );
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), ExecuteMsg::ClaimReferralRewards {});
+        assert!(matches!(err, Err(ContractError::NothingToClaim {})));
+        let stats = query_referral_stats(deps.as_ref(), "bob".to_string()).unwrap();
+        assert_eq!(stats.stats.earned, vec![Asset::from(Coin::new(4, "inj"))]);
+    }
+
+    #[test]
+    fn jackpot_pools_grow_pay_out_and_reseed() {
+        let mut deps = setup();
+        publish_box(&mut deps, create_box_msg("lucky"), 10);
+        let owner = mock_info("owner", &[]);
+        let jackpot_config = |jackpot_odds: u64| ExecuteMsg::UpdateJackpotConfig { jackpot_bps: 1000, jackpot_odds };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), jackpot_config(util::JACKPOT_ODDS_SCALE + 1));
+        assert!(matches!(err, Err(ContractError::InvalidJackpotConfig {})));
+        let greedy = |jackpot_bps: u64| ExecuteMsg::UpdateJackpotConfig { jackpot_bps, jackpot_odds: 0 };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), greedy(util::MAX_BPS - util::OPEN_FEE_BPS + 1));
+        assert!(matches!(err, Err(ContractError::InvalidJackpotConfig {})));
+        execute(deps.as_mut(), mock_env(), owner.clone(), jackpot_config(0)).unwrap();
+
+        // 10% of the price feeds the pool, out of the creator's cut
+        let open = ExecuteMsg::OpenBox { box_id: "lucky".to_string(), proof: None, referrer: None };
+        let paid = |sender: &str| mock_info(sender, &coins(100, "inj"));
+        let res = execute(deps.as_mut(), mock_env(), paid("alice"), open.clone()).unwrap();
+        assert!(
+            res.messages.iter().any(|msg| {
+                msg.msg ==
+                    CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: coins(85, "inj") })
+            })
+        );
+        execute(deps.as_mut(), mock_env(), paid("alice"), open.clone()).unwrap();
+        let pools = query_jackpots(deps.as_ref()).unwrap().pools;
+        assert_eq!(pools[0].balance, Asset::from(Coin::new(20, "inj")));
+
+        // Seeding draws on the 10 inj of fees collected so far
+        let seed = ExecuteMsg::SetJackpotSeed {
+            asset: AssetInfo::Native { denom: "inj".to_string() },
+            seed: Uint128::from(30u64),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), seed.clone());
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        execute(deps.as_mut(), mock_env(), owner.clone(), seed).unwrap();
+        assert_eq!(query_jackpots(deps.as_ref()).unwrap().pools[0].balance.amount, Uint128::from(30u64));
+        assert_eq!(query_treasury(deps.as_ref()).unwrap().balances[0].withdrawn, Uint128::from(10u64));
+
+        execute(deps.as_mut(), mock_env(), owner.clone(), jackpot_config(util::JACKPOT_ODDS_SCALE)).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), paid("bob"), open.clone()).unwrap();
+        assert!(
+            res.messages.iter().any(|msg| {
+                msg.msg == CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(40, "inj") })
+            })
+        );
+        // Only the 5 inj fee of the winning open is left to reseed with
+        assert_eq!(query_jackpots(deps.as_ref()).unwrap().pools[0].balance.amount, Uint128::from(5u64));
+        let wins = query_jackpot_history(deps.as_ref(), None, None).unwrap().wins;
+        assert_eq!(wins.len(), 1);
+        assert_eq!((wins[0].winner.as_str(), wins[0].prize.amount), ("bob", Uint128::from(40u64)));
+
+        // At the cap the jackpot takes everything but the fee, leaving the creator nothing
+        execute(deps.as_mut(), mock_env(), owner, greedy(util::MAX_BPS - util::OPEN_FEE_BPS)).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), paid("carol"), open).unwrap();
+        assert!(
+            !res.messages.iter().any(|msg| {
+                matches!(&msg.msg, CosmosMsg::Bank(BankMsg::Send { to_address, .. }) if to_address == "creator")
+            })
+        );
+        assert_eq!(query_jackpots(deps.as_ref()).unwrap().pools[0].balance.amount, Uint128::from(100u64));
+    }
+
+    #[test]
+    fn raffles_sell_tickets_and_draw_at_the_end() {
+        let mut deps = setup();
+        let creator = mock_info("creator", &coins(20, "inj"));
+        let raffle = |box_id: &str, min_tickets: u64| {
+            let mut msg = create_box_msg(box_id);
+            msg.duration = 100;
+            msg.kind = Some(BoxKind::Raffle { min_tickets: Some(min_tickets), max_tickets: Some(3) });
+            msg
+        };
+        let mut undated = create_box_msg("nodate");
+        undated.kind = Some(BoxKind::Raffle { min_tickets: None, max_tickets: None });
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(undated));
+        assert!(matches!(err, Err(ContractError::InvalidRaffle {})));
+        let mut huge = create_box_msg("huge");
+        huge.duration = 100;
+        huge.kind = Some(BoxKind::Raffle { min_tickets: None, max_tickets: Some(util::MAX_RAFFLE_TICKETS + 1) });
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(huge));
+        assert!(matches!(err, Err(ContractError::InvalidRaffle {})));
+        for box_id in ["raffle", "flop"] {
+            publish_box(&mut deps, raffle(box_id, 2), 2);
+        }
+
+        let buy = |box_id: &str| ExecuteMsg::OpenBox { box_id: box_id.to_string(), proof: None, referrer: None };
+        let paid = |sender: &str| mock_info(sender, &coins(100, "inj"));
+        for buyer in ["alice", "alice", "bob"] {
+            let res = execute(deps.as_mut(), mock_env(), paid(buyer), buy("raffle")).unwrap();
+            assert!(res.messages.is_empty());
+        }
+        let err = execute(deps.as_mut(), mock_env(), paid("carol"), buy("raffle"));
+        assert!(matches!(err, Err(ContractError::RaffleFull {})));
+        execute(deps.as_mut(), mock_env(), paid("carol"), buy("flop")).unwrap();
+        let tickets = query_raffle_tickets(deps.as_ref(), "raffle".to_string(), "alice".to_string()).unwrap();
+        assert_eq!((tickets.tickets, tickets.total), (2, 3));
+
+        let draw = |box_id: &str| ExecuteMsg::DrawRaffle { box_id: box_id.to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), draw("raffle"));
+        assert!(matches!(err, Err(ContractError::RaffleNotEnded {})));
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let res = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), draw("raffle")).unwrap();
+        let history = query_open_history(deps.as_ref(), "raffle".to_string(), None, None).unwrap();
+        let won: Vec<_> = history.opens.iter().filter(|x| x.reward_id.is_some()).collect();
+        assert_eq!(won.len(), 2);
+        let prizes = res.messages
+            .iter()
+            .filter(|msg| matches!(&msg.msg, CosmosMsg::Bank(BankMsg::Send { amount, .. }) if amount == &coins(10, "inj")))
+            .count();
+        assert_eq!(prizes, 2);
+        assert!(
+            res.messages.iter().any(|msg| {
+                msg.msg == CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: coins(285, "inj") })
+            })
+        );
+        assert_eq!(BOX_MAP.load(deps.as_ref().storage, "raffle".to_string()).unwrap().status, BoxStatus::SoldOut);
+        let err = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), draw("raffle"));
+        assert!(matches!(err, Err(ContractError::BoxTerminated {})));
+
+        // Below the minimum every ticket is refunded and the rewards go back
+        let res = execute(deps.as_mut(), env, mock_info("anyone", &[]), draw("flop")).unwrap();
+        assert_eq!(
+            res.messages.iter().map(|msg| msg.msg.clone()).collect::<Vec<_>>(),
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: "carol".to_string(), amount: coins(100, "inj") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: coins(20, "inj") })
+            ]
+        );
+        assert_eq!(BOX_MAP.load(deps.as_ref().storage, "flop".to_string()).unwrap().status, BoxStatus::Cancelled);
+    }
+
+    #[test]
+    fn raffle_winners_do_not_depend_on_the_draw_block() {
+        let winners = |delay: u64| {
+            let mut deps = setup();
+            let mut msg = create_box_msg("raffle");
+            msg.duration = 100;
+            msg.kind = Some(BoxKind::Raffle { min_tickets: None, max_tickets: None });
+            publish_box(&mut deps, msg, 1);
+            for (seconds, buyer) in [(0, "alice"), (10, "bob"), (20, "carol"), (30, "dave")] {
+                let mut env = mock_env();
+                env.block.time = env.block.time.plus_seconds(seconds);
+                let buy = ExecuteMsg::OpenBox { box_id: "raffle".to_string(), proof: None, referrer: None };
+                execute(deps.as_mut(), env, mock_info(buyer, &coins(100, "inj")), buy).unwrap();
+            }
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(100 + delay);
+            env.block.height += delay;
+            let draw = ExecuteMsg::DrawRaffle { box_id: "raffle".to_string() };
+            execute(deps.as_mut(), env, mock_info("anyone", &[]), draw).unwrap();
+            BOX_MAP.load(deps.as_ref().storage, "raffle".to_string()).unwrap().winners
+        };
+        let early = winners(0);
+        assert_eq!(early.as_ref().map(|x| x.len()), Some(1));
+        for delay in [1, 7, 5000] {
+            assert_eq!(winners(delay), early);
+        }
+    }
+
+    #[test]
+    fn raffle_sampling_is_uniform() {
+        let mut hits = [0u64; 3];
+        for round in 0..3000 {
+            let index = util::sample_index(b"seed", round, 3);
+            hits[index as usize] += 1;
+        }
+        assert!(hits.iter().all(|x| (900..1100).contains(x)), "{:?}", hits);
+        assert_eq!(util::sample_index(b"seed", 0, 1), 0);
+    }
+
+    #[test]
+    fn trade_ins_collect_nfts_before_opening() {
+        let mut deps = setup();
+        let mut msg = create_box_msg("crafting");
+        msg.trade_in = Some(TradeIn {
+            collections: vec!["commons".to_string(), "rares".to_string()],
+            count: 2,
+            sink: Some("sink".to_string()),
+        });
+        publish_box(&mut deps, msg, 10);
+
+        let open = ExecuteMsg::OpenBox { box_id: "crafting".to_string(), proof: None, referrer: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(100, "inj")), open);
+        assert!(matches!(err, Err(ContractError::TradeInRequired {})));
+
+        let trade_in = |sender: &str, token_id: &str| {
+            ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+                sender: sender.to_string(),
+                token_id: token_id.to_string(),
+                msg: to_json_binary(
+                    &(NftReceiveMsg::TradeIn { box_id: "crafting".to_string(), proof: None })
+                ).unwrap(),
+            })
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("punks", &[]), trade_in("alice", "1"));
+        assert!(matches!(err, Err(ContractError::InvalidTradeIn {})));
+
+        // The first NFT waits in escrow and can be taken back
+        let res = execute(deps.as_mut(), mock_env(), mock_info("commons", &[]), trade_in("alice", "1")).unwrap();
+        assert!(res.messages.is_empty());
+        execute(deps.as_mut(), mock_env(), mock_info("commons", &[]), trade_in("bob", "2")).unwrap();
+        let deposits = query_trade_in_deposits(deps.as_ref(), "crafting".to_string(), "bob".to_string()).unwrap();
+        assert_eq!((deposits.deposits.len(), deposits.required), (1, 2));
+        let withdraw = ExecuteMsg::WithdrawTradeIn { box_id: "crafting".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), withdraw.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "commons".to_string(),
+                msg: to_json_binary(
+                    &(cw721::Cw721ExecuteMsg::TransferNft { recipient: "bob".to_string(), token_id: "2".to_string() })
+                ).unwrap(),
+                funds: vec![],
+            })
+        );
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), withdraw);
+        assert!(matches!(err, Err(ContractError::NothingToClaim {})));
+
+        // The second NFT completes the trade-in, both go to the sink and the box opens
+        let res = execute(deps.as_mut(), mock_env(), mock_info("rares", &[]), trade_in("alice", "9")).unwrap();
+        let sunk = res.messages
+            .iter()
+            .filter(|msg| {
+                matches!(&msg.msg, CosmosMsg::Wasm(WasmMsg::Execute { msg, .. })
+                    if from_json::<cw721::Cw721ExecuteMsg>(msg).is_ok_and(|msg| {
+                        matches!(msg, cw721::Cw721ExecuteMsg::TransferNft { recipient, .. } if recipient == "sink")
+                    }))
+            })
+            .count();
+        assert_eq!(sunk, 2);
+        let history = query_open_history(deps.as_ref(), "crafting".to_string(), None, None).unwrap();
+        assert_eq!(
+            history.opens[0].traded_in,
+            vec![
+                TradedNft { collection: "commons".to_string(), token_id: "1".to_string() },
+                TradedNft { collection: "rares".to_string(), token_id: "9".to_string() }
+            ]
+        );
+        assert!(query_trade_in_deposits(deps.as_ref(), "crafting".to_string(), "alice".to_string()).unwrap().deposits.is_empty());
+    }
+
+    #[test]
+    fn rerolls_redraw_held_rewards_until_accepted() {
+        let mut deps = setup();
+        let mut msg = create_box_msg("again");
+        msg.reroll = Some(RerollConfig { fee: Uint128::from(20u64), window: u64::MAX });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::CreateBox(msg.clone()));
+        assert!(matches!(err, Err(ContractError::InvalidReroll {})));
+        msg.reroll = Some(RerollConfig { fee: Uint128::from(20u64), window: 60 });
+        publish_box(&mut deps, msg, 10);
+
+        let prize = |to: &str| CosmosMsg::Bank(BankMsg::Send { to_address: to.to_string(), amount: coins(10, "inj") });
+        let open = ExecuteMsg::OpenBox { box_id: "again".to_string(), proof: None, referrer: None };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(100, "inj")), open.clone()).unwrap();
+        assert!(!res.messages.iter().any(|msg| msg.msg == prize("alice")));
+        let pending = query_pending_reward(deps.as_ref(), "again".to_string(), "alice".to_string()).unwrap();
+        assert_eq!(pending.pending.as_ref().map(|x| x.expires_at), Some(mock_env().block.time.seconds() + 60));
+
+        let reroll = ExecuteMsg::Reroll { box_id: "again".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(100, "inj")), reroll.clone());
+        assert!(matches!(err, Err(ContractError::AmountNotMatch {})));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(20, "inj")), reroll.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: coins(19, "inj") })
+        );
+        let pending = query_pending_reward(deps.as_ref(), "again".to_string(), "alice".to_string()).unwrap();
+        assert_eq!(pending.pending.unwrap().rerolls, 1);
+        let fbox = BOX_MAP.load(deps.as_ref().storage, "again".to_string()).unwrap();
+        assert_eq!(fbox.rewards[0].count, 9);
+
+        // Others may only finalize the result once the window has passed
+        let accept = ExecuteMsg::Accept { box_id: "again".to_string(), address: Some("alice".to_string()) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), accept.clone());
+        assert!(matches!(err, Err(ContractError::RerollWindowOpen {})));
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(60);
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(20, "inj")), reroll);
+        assert!(matches!(err, Err(ContractError::RerollExpired {})));
+        let res = execute(deps.as_mut(), env, mock_info("bob", &[]), accept).unwrap();
+        assert_eq!(res.messages[0].msg, prize("alice"));
+        assert!(query_pending_reward(deps.as_ref(), "again".to_string(), "alice".to_string()).unwrap().pending.is_none());
+        let users = query_users(deps.as_ref()).unwrap().users;
+        let alice = users.iter().find(|x| x.address == "alice").unwrap();
+        assert_eq!(alice.rewards["again"].len(), 1);
+
+        // Opening again takes the result still held from the previous open
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(100, "inj")), open.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(100, "inj")), open).unwrap();
+        assert!(res.messages.iter().any(|msg| msg.msg == prize("alice")));
+    }
+
+    #[test]
+    fn bundles_are_deposited_piecewise_and_paid_together() {
+        let mut deps = setup();
+        ALLOWED_ASSETS.save(
+            deps.as_mut().storage,
+            "usdc".to_string(),
+            &(AllowedAsset {
+                info: AssetInfo::Cw20 { contract_addr: Addr::unchecked("usdc") },
+                decimals: 6,
+            })
+        ).unwrap();
+        let creator = mock_info("creator", &coins(30, "inj"));
+        let items = vec![
+            BundleItem::Nft { collection: "punks".to_string(), token_id: "7".to_string() },
+            BundleItem::Token {
+                asset: Asset { info: AssetInfo::Native { denom: "inj".to_string() }, amount: Uint128::from(100u64) },
+            },
+            BundleItem::Token {
+                asset: Asset { info: AssetInfo::Cw20 { contract_addr: Addr::unchecked("usdc") }, amount: Uint128::from(5u64) },
+            }
+        ];
+        let add = |box_id: &str, items: Vec<BundleItem>| ExecuteMsg::AddBundleReward {
+            box_id: box_id.to_string(),
+            odds: 100,
+            items,
+        };
+        execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(create_box_msg("bundle"))).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), add("bundle", vec![]));
+        assert!(matches!(err, Err(ContractError::InvalidBundle {})));
+        execute(deps.as_mut(), mock_env(), creator.clone(), add("bundle", items.clone())).unwrap();
+
+        let publish = |box_id: &str| ExecuteMsg::PublishBox { box_id: box_id.to_string(), start_time: None };
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), publish("bundle"));
+        assert!(matches!(err, Err(ContractError::BundleIncomplete {})));
+
+        let deposit = |box_id: &str, bundle_id: u64| ExecuteMsg::DepositBundle { box_id: box_id.to_string(), bundle_id };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &coins(50, "inj")), deposit("bundle", 1));
+        assert!(matches!(err, Err(ContractError::InvalidBundle {})));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &coins(100, "inj")), deposit("bundle", 1)).unwrap();
+        let cw20 = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "creator".to_string(),
+            amount: Uint128::from(5u64),
+            msg: to_json_binary(&(TokenReceiveMsg::DepositBundle { box_id: "bundle".to_string(), bundle_id: 1 })).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("usdc", &[]), cw20).unwrap();
+        let pending = query_pending_bundles(deps.as_ref(), "bundle".to_string()).unwrap();
+        assert_eq!(pending.bundles[0].deposited, vec![false, true, true]);
+
+        let nft = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "creator".to_string(),
+            token_id: "7".to_string(),
+            msg: to_json_binary(&(NftReceiveMsg::DepositBundle { box_id: "bundle".to_string(), bundle_id: 1 })).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("punks", &[]), nft).unwrap();
+        assert!(query_pending_bundles(deps.as_ref(), "bundle".to_string()).unwrap().bundles.is_empty());
+        let fbox = BOX_MAP.load(deps.as_ref().storage, "bundle".to_string()).unwrap();
+        assert_eq!((fbox.rewards[0].id, fbox.rewards[0].reward_type.clone()), (1, RewardType::Bundle));
+        let value = query_expected_value(deps.as_ref(), "bundle".to_string()).unwrap();
+        assert_eq!(value.nft_probability, Decimal::one());
+        assert_eq!(value.tokens.len(), 2);
+
+        execute(deps.as_mut(), mock_env(), creator.clone(), publish("bundle")).unwrap();
+        let open = ExecuteMsg::OpenBox { box_id: "bundle".to_string(), proof: None, referrer: None };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player", &coins(100, "inj")), open).unwrap();
+        let paid = |msg: &CosmosMsg| match msg {
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => to_address == "player" && amount == &coins(100, "inj"),
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) if contract_addr == "punks" =>
+                msg == &to_json_binary(&Cw721ExecuteMsg::TransferNft { recipient: "player".to_string(), token_id: "7".to_string() }).unwrap(),
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) if contract_addr == "usdc" =>
+                msg == &to_json_binary(&Cw20ExecuteMsg::Transfer { recipient: "player".to_string(), amount: Uint128::from(5u64) }).unwrap(),
+            _ => false,
+        };
+        assert_eq!(res.messages.iter().filter(|x| paid(&x.msg)).count(), 3);
+
+        // Unfinished bundles hand back whatever was deposited
+        execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(create_box_msg("partial"))).unwrap();
+        for _ in 0..2 {
+            execute(deps.as_mut(), mock_env(), creator.clone(), add("partial", items.clone())).unwrap();
+        }
+        for bundle_id in [1, 2] {
+            execute(deps.as_mut(), mock_env(), mock_info("creator", &coins(100, "inj")), deposit("partial", bundle_id)).unwrap();
+        }
+        let refund = CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: coins(100, "inj") });
+        let cancel = ExecuteMsg::CancelBundle { box_id: "partial".to_string(), bundle_id: 1 };
+        let res = execute(deps.as_mut(), mock_env(), creator.clone(), cancel.clone()).unwrap();
+        assert_eq!(res.messages.iter().map(|x| x.msg.clone()).collect::<Vec<_>>(), vec![refund.clone()]);
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), cancel);
+        assert!(matches!(err, Err(ContractError::BundleNotFound {})));
+        let cancel_box = ExecuteMsg::CancelBox { box_id: "partial".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), creator, cancel_box).unwrap();
+        assert_eq!(res.messages.iter().map(|x| x.msg.clone()).collect::<Vec<_>>(), vec![refund]);
+        assert!(query_pending_bundles(deps.as_ref(), "partial".to_string()).unwrap().bundles.is_empty());
+    }
+
+    #[test]
+    fn series_boxes_share_their_reward_stock() {
+        let mut deps = setup();
+        let creator = mock_info("creator", &coins(30, "inj"));
+        for box_id in ["bronze", "gold"] {
+            let mut msg = create_box_msg(box_id);
+            msg.open_limits = Some(OpenLimits { per_address: None, per_address_daily: None, total: Some(1) });
+            execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(msg)).unwrap();
+        }
+        for box_id in ["silver", "tin"] {
+            execute(deps.as_mut(), mock_env(), creator.clone(), ExecuteMsg::CreateBox(create_box_msg(box_id))).unwrap();
+        }
+        let rewards = vec![TokenFactoryReward {
+            id: 0,
+            odds: 100,
+            token_denom: "inj".to_string(),
+            token_decimals: 18,
+            reward_type: RewardType::TokenFactory,
+            amount: Uint128::from(10u64),
+            count: 3,
+        }];
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            creator.clone(),
+            ExecuteMsg::AddTokenFactoryReward { box_id: "bronze".to_string(), rewards: rewards.clone() }
+        ).unwrap();
+        let create = ExecuteMsg::CreateSeries { series_id: "season".to_string(), box_id: "bronze".to_string() };
+        execute(deps.as_mut(), mock_env(), creator.clone(), create).unwrap();
+
+        let join = |box_id: &str, odds: Vec<SeriesOdds>| ExecuteMsg::JoinSeries {
+            series_id: "season".to_string(),
+            box_id: box_id.to_string(),
+            odds,
+        };
+        let err = execute(deps.as_mut(), mock_env(), creator.clone(), join("gold", vec![SeriesOdds { reward_id: 9, odds: 500 }]));
+        assert!(matches!(err, Err(ContractError::RewardNotFound {})));
+        for box_id in ["gold", "silver", "tin"] {
+            execute(deps.as_mut(), mock_env(), creator.clone(), join(box_id, vec![SeriesOdds { reward_id: 1, odds: 500 }])).unwrap();
+        }
+        let gold = BOX_MAP.load(deps.as_ref().storage, "gold".to_string()).unwrap();
+        assert_eq!((gold.rewards[0].odds, gold.rewards[0].count), (500, 3));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            creator.clone(),
+            ExecuteMsg::AddTokenFactoryReward { box_id: "gold".to_string(), rewards }
+        );
+        assert!(matches!(err, Err(ContractError::SeriesBox {})));
+
+        for box_id in ["bronze", "gold", "silver", "tin"] {
+            let publish = ExecuteMsg::PublishBox { box_id: box_id.to_string(), start_time: None };
+            execute(deps.as_mut(), mock_env(), creator.clone(), publish).unwrap();
+        }
+        let prize = CosmosMsg::Bank(BankMsg::Send { to_address: "player".to_string(), amount: coins(10, "inj") });
+        let leftover = CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: coins(10, "inj") });
+        for box_id in ["gold", "bronze"] {
+            let open = ExecuteMsg::OpenBox { box_id: box_id.to_string(), proof: None, referrer: None };
+            let res = execute(deps.as_mut(), mock_env(), mock_info("player", &coins(100, "inj")), open).unwrap();
+            // Selling out one box leaves the stock to the rest of the series
+            let msgs: Vec<_> = res.messages.iter().map(|x| x.msg.clone()).collect();
+            assert!(msgs.contains(&prize) && !msgs.contains(&leftover));
+        }
+        let series = query_series(deps.as_ref(), "season".to_string()).unwrap().series;
+        assert_eq!(series.rewards[0].count, 1);
+        let gold = BOX_MAP.load(deps.as_ref().storage, "gold".to_string()).unwrap();
+        assert_eq!((gold.status, gold.rewards[0].count), (BoxStatus::SoldOut, 1));
+        assert_eq!(util::escrowed_amount(deps.as_ref(), &(AssetInfo::Native { denom: "inj".to_string() })).unwrap(), Uint128::from(10u64));
+
+        // The last of the stock sells out every box of the series, not just the one opened
+        let open = ExecuteMsg::OpenBox { box_id: "silver".to_string(), proof: None, referrer: None };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player", &coins(100, "inj")), open).unwrap();
+        assert!(res.messages.iter().any(|x| x.msg == prize));
+        for box_id in ["silver", "tin"] {
+            let fbox = BOX_MAP.load(deps.as_ref().storage, box_id.to_string()).unwrap();
+            assert_eq!((fbox.status, fbox.rewards[0].count), (BoxStatus::SoldOut, 0));
+        }
+        let open = ExecuteMsg::OpenBox { box_id: "tin".to_string(), proof: None, referrer: None };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player", &coins(100, "inj")), open).is_err());
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            creator.clone(),
+            ExecuteMsg::CloseSeries { series_id: "season".to_string() }
+        ).unwrap();
+        assert!(res.messages.iter().all(|x| x.msg != leftover));
+        let bronze = BOX_MAP.load(deps.as_ref().storage, "bronze".to_string()).unwrap();
+        assert_eq!(bronze.rewards[0].count, 0);
+        assert_eq!(util::escrowed_amount(deps.as_ref(), &(AssetInfo::Native { denom: "inj".to_string() })).unwrap(), Uint128::zero());
+    }
+
+    #[test]
+    fn cw20_rewards_match_the_deposit() {
+        let mut deps = setup();
+        ALLOWED_ASSETS.save(
+            deps.as_mut().storage,
+            "usdc".to_string(),
+            &(AllowedAsset {
+                info: AssetInfo::Cw20 { contract_addr: Addr::unchecked("usdc") },
+                decimals: 6,
+            })
+        ).unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::CreateBox(create_box_msg("stable"))
+        ).unwrap();
+        let deposit = |sender: &str, box_id: &str, sent: u64| ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::from(sent),
+            msg: to_json_binary(
+                &(TokenReceiveMsg::CreateFortuneBoxWithCoin {
+                    box_id: box_id.to_string(),
+                    creator: "creator".to_string(),
+                    denom: "usdc".to_string(),
+                    amount: Uint128::from(10u64),
+                    odds: 10,
+                    price: create_box_msg(box_id).price,
+                    duration: 0,
+                })
+            ).unwrap(),
+        });
+        let usdc = mock_info("usdc", &[]);
+        let err = execute(deps.as_mut(), mock_env(), usdc.clone(), deposit("creator", "stable", 1));
+        assert!(matches!(err, Err(ContractError::AmountNotMatch {})));
+        let err = execute(deps.as_mut(), mock_env(), usdc.clone(), deposit("creator", "fresh", 1));
+        assert!(matches!(err, Err(ContractError::AmountNotMatch {})));
+        let err = execute(deps.as_mut(), mock_env(), usdc.clone(), deposit("mallory", "stable", 10));
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+
+        execute(deps.as_mut(), mock_env(), usdc.clone(), deposit("creator", "stable", 10)).unwrap();
+        execute(deps.as_mut(), mock_env(), usdc, deposit("creator", "fresh", 10)).unwrap();
+        for box_id in ["stable", "fresh"] {
+            let fbox = BOX_MAP.load(deps.as_ref().storage, box_id.to_string()).unwrap();
+            assert_eq!(fbox.rewards[0].asset.as_ref().unwrap().amount, Uint128::from(10u64));
+        }
+    }
+
+    #[test]
+    fn only_the_creator_adds_native_and_nft_rewards() {
+        let mut deps = setup();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::CreateBox(create_box_msg("mine"))
+        ).unwrap();
+        let rewards = |odds: u64| {
+            vec![TokenFactoryReward {
+                id: 0,
+                odds,
+                token_denom: "inj".to_string(),
+                token_decimals: 18,
+                reward_type: RewardType::TokenFactory,
+                amount: Uint128::from(10u64),
+                count: 1,
+            }]
+        };
+        let add = |odds: u64| ExecuteMsg::AddTokenFactoryReward { box_id: "mine".to_string(), rewards: rewards(odds) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("mallory", &coins(10, "inj")), add(10));
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        let mut overflow = rewards(u64::MAX);
+        overflow.extend(rewards(1));
+        let add_overflow = ExecuteMsg::AddTokenFactoryReward { box_id: "mine".to_string(), rewards: overflow };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &coins(20, "inj")), add_overflow);
+        assert!(matches!(err, Err(ContractError::MaxOddsReached { .. })));
+
+        let nft = |sender: &str, nft_id: &str| ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: sender.to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(
+                &(NftReceiveMsg::AddNftReward {
+                    box_id: "mine".to_string(),
+                    collection_addr: "punks".to_string(),
+                    nft_id: nft_id.to_string(),
+                    odds: 10,
+                })
+            ).unwrap(),
+        });
+        let punks = mock_info("punks", &[]);
+        let err = execute(deps.as_mut(), mock_env(), punks.clone(), nft("mallory", "1"));
+        assert!(matches!(err, Err(ContractError::Unauthorized {})));
+        let err = execute(deps.as_mut(), mock_env(), punks.clone(), nft("creator", "2"));
+        assert!(matches!(err, Err(ContractError::InvalidCw721Token {})));
+        execute(deps.as_mut(), mock_env(), punks, nft("creator", "1")).unwrap();
+        let fbox = BOX_MAP.load(deps.as_ref().storage, "mine".to_string()).unwrap();
+        assert_eq!(fbox.rewards[0].nft_id, Some("1".to_string()));
     }
 }
@@ -1,11 +1,35 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{
-    to_json_binary, Addr, BalanceResponse as NativeBalanceResponse, BankMsg, BankQuery, Coin,
-    CosmosMsg, QuerierWrapper, QueryRequest, Response, StdResult, Storage, Uint128, WasmMsg,
-    WasmQuery,
+    to_json_binary, Addr, Api, BalanceResponse as NativeBalanceResponse, BankMsg, BankQuery, Coin,
+    CosmosMsg, Decimal, Deps, DepsMut, Env, HexBinary, MessageInfo, Order, QuerierWrapper,
+    QueryRequest, Response, StdResult, Storage, Uint128, WasmMsg, WasmQuery,
+};
+use cw20::{
+    BalanceResponse as CW20BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, TokenInfoResponse,
 };
-use cw20::{BalanceResponse as CW20BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+use cw721::{Cw721ExecuteMsg, Cw721QueryMsg, TokensResponse};
+use sha2::{Digest, Sha256};
 
-use crate::{state::CONFIG, ContractError};
+use crate::{
+    asset::{AllowedAsset, Asset, AssetInfo},
+    merkle,
+    msg::{
+        AllowlistProof, BoxKey, BoxStatus, BundleItem, CreditScope, HolderRequirement, KeyUsed,
+        OddsModel, OpenAllowanceResponse, RerollConfig, RewardData, RewardType, SeriesOdds,
+        TradeIn, TradedNft,
+    },
+    state::{
+        CreditGrant, DailyOpens, FortuneBox, JackpotPool, JackpotWin, PendingBundle, PendingReward,
+        ReferralStats, Series, UserInfo, ACCOUNT_MAP, ALLOWED_ASSETS, BOX_COUNT, BOX_MAP,
+        BOX_OPENS, CONFIG, CREDITS, DAILY_OPENS, ESCROW, FEATURED, JACKPOTS, JACKPOT_WINS,
+        JACKPOT_WIN_COUNT, OPEN_HISTORY, PENDING_BUNDLES, PENDING_REWARDS, REFERRALS,
+        REFERRAL_BALANCES, SERIES, TREASURY,
+    },
+    ContractError,
+};
 
 pub fn check_owner(storage: &mut dyn Storage, address: Addr) -> Result<Response, ContractError> {
     let cfg = CONFIG.load(storage)?;
@@ -16,6 +40,678 @@ pub fn check_owner(storage: &mut dyn Storage, address: Addr) -> Result<Response,
     Ok(Response::new().add_attribute("action", "check_owner"))
 }
 
+/// Validates a user supplied address, naming the offending field on failure.
+pub fn validate_addr(api: &dyn Api, field: &str, address: &str) -> Result<Addr, ContractError> {
+    api.addr_validate(address)
+        .map_err(|_| ContractError::InvalidAddress {
+            field: field.to_string(),
+            address: address.to_string(),
+        })
+}
+
+pub const BOX_ID_MIN_LEN: usize = 3;
+pub const BOX_ID_MAX_LEN: usize = 32;
+
+/// Caller chosen box ids are short lowercase slugs. All-digit ids are reserved for the ones
+/// issued by `next_box_id`.
+pub fn validate_box_id(box_id: &str) -> Result<(), ContractError> {
+    let valid = (BOX_ID_MIN_LEN..=BOX_ID_MAX_LEN).contains(&box_id.len())
+        && box_id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        && !box_id.chars().all(|c| c.is_ascii_digit());
+    if !valid {
+        return Err(ContractError::InvalidBoxId {
+            id: box_id.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Issues the next free numeric box id.
+pub fn next_box_id(storage: &mut dyn Storage) -> StdResult<String> {
+    let mut count = BOX_COUNT.may_load(storage)?.unwrap_or_default();
+    loop {
+        count += 1;
+        // Boxes created before ids were validated may already use a plain number
+        if !BOX_MAP.has(storage, count.to_string()) {
+            break;
+        }
+    }
+    BOX_COUNT.save(storage, &count)?;
+    Ok(count.to_string())
+}
+
+pub fn check_merkle_root(root: &Option<HexBinary>) -> Result<(), ContractError> {
+    if root.as_ref().is_some_and(|root| root.len() != 32) {
+        return Err(ContractError::InvalidMerkleRoot {});
+    }
+    Ok(())
+}
+
+/// Checks `opener` against the box allowlist, returning the open limit of its leaf.
+pub fn check_allowlist(
+    fbox: &FortuneBox,
+    opener: &Addr,
+    proof: Option<AllowlistProof>,
+) -> Result<Option<u64>, ContractError> {
+    let root = match &fbox.allowlist_root {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+    let proof = match proof {
+        Some(proof) => proof,
+        None => return Err(ContractError::NotAllowlisted {}),
+    };
+
+    let leaf = merkle::leaf_hash(opener.as_str(), proof.limit);
+    if !verify_proof(root, leaf, &proof.proof)? {
+        return Err(ContractError::NotAllowlisted {});
+    }
+    Ok(proof.limit)
+}
+
+/// Verifies a hex encoded merkle proof, nodes of the wrong length never verify.
+pub fn verify_proof(
+    root: &HexBinary,
+    leaf: merkle::Hash,
+    proof: &[HexBinary],
+) -> Result<bool, ContractError> {
+    let root: merkle::Hash = match root.as_slice().try_into() {
+        Ok(root) => root,
+        Err(_) => return Err(ContractError::InvalidMerkleRoot {}),
+    };
+    let mut path = Vec::with_capacity(proof.len());
+    for node in proof.iter() {
+        match node.as_slice().try_into() {
+            Ok(node) => path.push(node),
+            Err(_) => return Ok(false),
+        }
+    }
+    Ok(merkle::verify(&root, leaf, &path))
+}
+
+pub fn credit_scope_key(scope: &CreditScope) -> String {
+    match scope {
+        CreditScope::Box { box_id } => format!("box:{}", box_id),
+        CreditScope::Creator { creator } => format!("creator:{}", creator),
+    }
+}
+
+/// Validates `scope` and checks `sender` may hand out credits for it.
+pub fn check_credit_issuer(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    scope: CreditScope,
+    sender: Addr,
+) -> Result<CreditScope, ContractError> {
+    match scope {
+        CreditScope::Box { box_id } => {
+            let fbox = match BOX_MAP.may_load(storage, box_id.clone())? {
+                Some(fbox) => fbox,
+                None => return Err(ContractError::BoxNotFound {}),
+            };
+            check_box_admin(storage, &fbox, sender)?;
+            Ok(CreditScope::Box { box_id })
+        }
+        CreditScope::Creator { creator } => {
+            let creator = validate_addr(api, "creator", &creator)?;
+            if creator != sender {
+                check_owner(storage, sender)?;
+            }
+            Ok(CreditScope::Creator {
+                creator: creator.into_string(),
+            })
+        }
+    }
+}
+
+/// Adds `count` credits, a later expiry (or none) of the new grant extends the existing one.
+pub fn add_credits(
+    storage: &mut dyn Storage,
+    address: Addr,
+    scope: CreditScope,
+    count: u64,
+    expires_at: Option<u64>,
+) -> Result<(), ContractError> {
+    if count == 0 {
+        return Err(ContractError::InvalidCreditCount {});
+    }
+    let key = (address, credit_scope_key(&scope));
+    let grant = match CREDITS.may_load(storage, key.clone())? {
+        Some(grant) => CreditGrant {
+            scope,
+            count: grant
+                .count
+                .checked_add(count)
+                .ok_or(ContractError::InvalidCreditCount {})?,
+            expires_at: match (grant.expires_at, expires_at) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                _ => None,
+            },
+        },
+        None => CreditGrant {
+            scope,
+            count,
+            expires_at,
+        },
+    };
+    CREDITS.save(storage, key, &grant)?;
+    Ok(())
+}
+
+/// Spends one credit of `opener` on the box, preferring credits scoped to the box itself.
+pub fn consume_credit(
+    storage: &mut dyn Storage,
+    fbox: &FortuneBox,
+    opener: &Addr,
+    now: u64,
+) -> StdResult<Option<CreditScope>> {
+    let scopes = [
+        CreditScope::Box {
+            box_id: fbox.id.clone(),
+        },
+        CreditScope::Creator {
+            creator: fbox.creator.to_string(),
+        },
+    ];
+    for scope in scopes {
+        let key = (opener.clone(), credit_scope_key(&scope));
+        let mut grant = match CREDITS.may_load(storage, key.clone())? {
+            Some(grant) => grant,
+            None => continue,
+        };
+        if grant.count == 0 || grant.expires_at.is_some_and(|expires_at| now >= expires_at) {
+            CREDITS.remove(storage, key);
+            continue;
+        }
+        grant.count -= 1;
+        if grant.count == 0 {
+            CREDITS.remove(storage, key);
+        } else {
+            CREDITS.save(storage, key, &grant)?;
+        }
+        return Ok(Some(scope));
+    }
+    Ok(None)
+}
+
+pub fn validate_holder_gate(
+    api: &dyn Api,
+    gate: Vec<HolderRequirement>,
+) -> Result<Vec<HolderRequirement>, ContractError> {
+    gate.into_iter()
+        .map(|requirement| match requirement {
+            HolderRequirement::Nft { collection } => Ok(HolderRequirement::Nft {
+                collection: validate_addr(api, "collection", &collection)?.into_string(),
+            }),
+            HolderRequirement::Balance { asset } => Ok(HolderRequirement::Balance {
+                asset: Asset {
+                    info: asset.info.validate(api)?,
+                    amount: asset.amount,
+                },
+            }),
+        })
+        .collect()
+}
+
+/// Checks `opener` meets at least one holder requirement of the box. Failing queries count as
+/// not holding, so a broken collection can not lock out holders of the others.
+pub fn check_holder_gate(
+    querier: QuerierWrapper,
+    fbox: &FortuneBox,
+    opener: &Addr,
+) -> Result<(), ContractError> {
+    if fbox.holder_gate.is_empty() {
+        return Ok(());
+    }
+
+    let eligible = fbox
+        .holder_gate
+        .iter()
+        .any(|requirement| match requirement {
+            HolderRequirement::Nft { collection } => querier
+                .query_wasm_smart::<TokensResponse>(
+                    collection,
+                    &(Cw721QueryMsg::Tokens {
+                        owner: opener.to_string(),
+                        start_after: None,
+                        limit: Some(1),
+                    }),
+                )
+                .is_ok_and(|res| !res.tokens.is_empty()),
+            HolderRequirement::Balance { asset } => {
+                get_token_amount(querier, &asset.info, opener.clone())
+                    .is_ok_and(|balance| balance >= asset.amount)
+            }
+        });
+    if !eligible {
+        return Err(ContractError::NotEligible {});
+    }
+    Ok(())
+}
+
+const DAY_SECONDS: u64 = 86400;
+
+/// Opens `address` has left in a box at `now` under its open limits.
+pub fn open_allowance(
+    storage: &dyn Storage,
+    fbox: &FortuneBox,
+    address: Addr,
+    now: u64,
+) -> StdResult<OpenAllowanceResponse> {
+    let opened = BOX_OPENS
+        .may_load(storage, (fbox.id.clone(), address.clone()))?
+        .unwrap_or_default();
+    let opened_today = DAILY_OPENS
+        .may_load(storage, (fbox.id.clone(), address.clone()))?
+        .filter(|daily| daily.day == now / DAY_SECONDS)
+        .map_or(0, |daily| daily.count);
+
+    let limits = &fbox.open_limits;
+    let remaining_for_address = limits.per_address.map(|x| x.saturating_sub(opened));
+    let remaining_today = limits
+        .per_address_daily
+        .map(|x| x.saturating_sub(opened_today));
+    let remaining_total = limits.total.map(|x| x.saturating_sub(fbox.opened));
+    let remaining = [remaining_for_address, remaining_today, remaining_total]
+        .iter()
+        .flatten()
+        .min()
+        .copied();
+
+    Ok(OpenAllowanceResponse {
+        box_id: fbox.id.clone(),
+        address,
+        opened,
+        opened_today,
+        remaining_for_address,
+        remaining_today,
+        remaining_total,
+        remaining,
+    })
+}
+
+/// Enforces the box open limits and the allowlist `allowlist_limit` for one more open by
+/// `opener`, then counts it.
+pub fn record_open(
+    storage: &mut dyn Storage,
+    fbox: &FortuneBox,
+    opener: &Addr,
+    allowlist_limit: Option<u64>,
+    now: u64,
+) -> Result<(), ContractError> {
+    let allowance = open_allowance(storage, fbox, opener.clone(), now)?;
+    if allowance.remaining_total == Some(0) {
+        return Err(ContractError::BoxOpenCapReached {});
+    }
+    if allowance.remaining_for_address == Some(0)
+        || allowlist_limit.is_some_and(|limit| allowance.opened >= limit)
+    {
+        return Err(ContractError::OpenLimitReached {});
+    }
+    if allowance.remaining_today == Some(0) {
+        return Err(ContractError::DailyLimitReached {});
+    }
+
+    let key = (fbox.id.clone(), opener.clone());
+    BOX_OPENS.save(storage, key.clone(), &(allowance.opened + 1))?;
+    DAILY_OPENS.save(
+        storage,
+        key,
+        &(DailyOpens {
+            day: now / DAY_SECONDS,
+            count: allowance.opened_today + 1,
+        }),
+    )?;
+    Ok(())
+}
+
+fn load_user(storage: &dyn Storage, address: Addr) -> StdResult<UserInfo> {
+    Ok(ACCOUNT_MAP
+        .may_load(storage, address.clone())?
+        .unwrap_or(UserInfo {
+            address,
+            box_created: 0,
+            inj_spent: Uint128::zero(),
+            tokens_spent: Uint128::zero(),
+            box_opened: 0,
+            rewards: HashMap::new(),
+            points: Uint128::zero(),
+        }))
+}
+
+/// Books an open of `box_id` on the account of `opener`.
+pub fn record_user_open(
+    storage: &mut dyn Storage,
+    opener: Addr,
+    box_id: &str,
+    reward_id: Option<u64>,
+    native_spent: Uint128,
+    points: Uint128,
+) -> StdResult<()> {
+    let mut userinfo = load_user(storage, opener.clone())?;
+    userinfo.box_opened += 1;
+    if let Some(reward_id) = reward_id {
+        userinfo
+            .rewards
+            .entry(box_id.to_string())
+            .or_default()
+            .push(reward_id);
+    }
+    userinfo.inj_spent += native_spent;
+    userinfo.points += points;
+    ACCOUNT_MAP.save(storage, opener, &userinfo)
+}
+
+/// Books a result settled after its open was counted, a raffle win or a finalized reroll.
+pub fn record_user_reward(
+    storage: &mut dyn Storage,
+    winner: Addr,
+    box_id: &str,
+    reward_id: Option<u64>,
+    points: Uint128,
+) -> StdResult<()> {
+    let mut userinfo = load_user(storage, winner.clone())?;
+    if let Some(reward_id) = reward_id {
+        userinfo
+            .rewards
+            .entry(box_id.to_string())
+            .or_default()
+            .push(reward_id);
+    }
+    userinfo.points += points;
+    ACCOUNT_MAP.save(storage, winner, &userinfo)
+}
+
+pub fn validate_reroll(reroll: RerollConfig) -> Result<RerollConfig, ContractError> {
+    if reroll.fee.is_zero() || reroll.window == 0 || reroll.window > MAX_DURATION {
+        return Err(ContractError::InvalidReroll {});
+    }
+    Ok(reroll)
+}
+
+/// Pays a pending reward out to its opener.
+pub fn finalize_pending(
+    storage: &mut dyn Storage,
+    pending: PendingReward,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    PENDING_REWARDS.remove(storage, (pending.box_id.clone(), pending.opener.clone()));
+    record_user_reward(
+        storage,
+        pending.opener.clone(),
+        &pending.box_id,
+        Some(pending.reward.id),
+        Uint128::zero(),
+    )?;
+    reward_transfer_messages(storage, &pending.reward, 1, pending.opener)
+}
+
+/// Pays every raffle ticket of the box back to its buyer.
+pub fn refund_tickets(
+    storage: &mut dyn Storage,
+    fbox: &mut FortuneBox,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    if fbox.raffle_pot.is_zero() {
+        return Ok(vec![]);
+    }
+    let mut msgs = vec![];
+    for item in OPEN_HISTORY
+        .prefix(fbox.id.clone())
+        .range(storage, None, None, Order::Ascending)
+    {
+        let (_, record) = item?;
+        if let Some(payment) = record.payment {
+            msgs.push(transfer_token_message(payment, record.opener)?);
+        }
+    }
+    release_escrow(
+        storage,
+        &Asset {
+            info: fbox.price.info.clone(),
+            amount: fbox.raffle_pot,
+        },
+    )?;
+    fbox.raffle_pot = Uint128::zero();
+    Ok(msgs)
+}
+
+/// Seed of a raffle draw, made only of what is fixed once the raffle ends: the box, its end and
+/// every ticket sold. Whoever submits the draw, in whichever block, gets the same winners.
+pub fn raffle_seed(storage: &dyn Storage, fbox: &FortuneBox) -> StdResult<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    hasher.update(fbox.id.as_bytes());
+    hasher.update(fbox.end_time.unwrap_or_default().to_be_bytes());
+    for item in OPEN_HISTORY
+        .prefix(fbox.id.clone())
+        .range(storage, None, None, Order::Ascending)
+    {
+        let (index, record) = item?;
+        hasher.update(index.to_be_bytes());
+        hasher.update(record.opener.as_bytes());
+        hasher.update(record.time.to_be_bytes());
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Uniform index below `n` from `seed`, rehashing draws from the top of the u64 range that
+/// would favour small indexes.
+pub fn sample_index(seed: &[u8], round: u64, n: u64) -> u64 {
+    let rem = (u64::MAX % n + 1) % n;
+    let mut attempt = 0u64;
+    loop {
+        let mut hasher = Sha256::new();
+        hasher.update(seed);
+        hasher.update(round.to_be_bytes());
+        hasher.update(attempt.to_be_bytes());
+        let hash = hasher.finalize();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&hash[0..8]);
+        let value = u64::from_be_bytes(bytes);
+        if value <= u64::MAX - rem {
+            return value % n;
+        }
+        attempt += 1;
+    }
+}
+
+/// Burn message of cw721-base, which the cw721 spec crate does not include.
+#[cw_serde]
+enum Cw721BurnMsg {
+    Burn { token_id: String },
+}
+
+pub fn validate_box_key(api: &dyn Api, key: BoxKey) -> Result<BoxKey, ContractError> {
+    match key {
+        BoxKey::Nft { collection, burn } => Ok(BoxKey::Nft {
+            collection: validate_addr(api, "collection", &collection)?.into_string(),
+            burn,
+        }),
+        BoxKey::Cw20 {
+            token,
+            amount,
+            burn,
+        } => {
+            if amount.is_zero() {
+                return Err(ContractError::InvalidKey {});
+            }
+            Ok(BoxKey::Cw20 {
+                token: validate_addr(api, "token", &token)?.into_string(),
+                amount,
+                burn,
+            })
+        }
+    }
+}
+
+/// Spends `used` on one open of the box, returning the burn message or holding the key.
+pub fn use_key(
+    storage: &mut dyn Storage,
+    fbox: &mut FortuneBox,
+    used: &KeyUsed,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let burn = match (&fbox.key, used) {
+        (
+            Some(BoxKey::Nft { collection, burn }),
+            KeyUsed::Nft {
+                collection: sent, ..
+            },
+        ) if collection == sent => *burn,
+        (
+            Some(BoxKey::Cw20 {
+                token,
+                amount,
+                burn,
+            }),
+            KeyUsed::Cw20 {
+                token: sent,
+                amount: sent_amount,
+            },
+        ) if token == sent && amount == sent_amount => *burn,
+        _ => return Err(ContractError::InvalidKey {}),
+    };
+
+    if !burn {
+        match used {
+            KeyUsed::Nft { token_id, .. } => fbox.held_keys.push(token_id.clone()),
+            KeyUsed::Cw20 { token, amount } => {
+                fbox.held_key_amount += *amount;
+                escrow(
+                    storage,
+                    &Asset {
+                        info: AssetInfo::Cw20 {
+                            contract_addr: Addr::unchecked(token),
+                        },
+                        amount: *amount,
+                    },
+                )?;
+            }
+        }
+        return Ok(vec![]);
+    }
+    let (contract_addr, msg) = match used {
+        KeyUsed::Nft {
+            collection,
+            token_id,
+        } => (
+            collection.clone(),
+            to_json_binary(&Cw721BurnMsg::Burn {
+                token_id: token_id.clone(),
+            })?,
+        ),
+        KeyUsed::Cw20 { token, amount } => (
+            token.clone(),
+            to_json_binary(&Cw20ExecuteMsg::Burn { amount: *amount })?,
+        ),
+    };
+    Ok(vec![CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr,
+        msg,
+        funds: vec![],
+    })])
+}
+
+pub fn validate_trade_in(api: &dyn Api, trade_in: TradeIn) -> Result<TradeIn, ContractError> {
+    if trade_in.count == 0 || trade_in.collections.is_empty() {
+        return Err(ContractError::InvalidTradeIn {});
+    }
+    let mut collections = Vec::with_capacity(trade_in.collections.len());
+    for collection in trade_in.collections.iter() {
+        collections.push(validate_addr(api, "collection", collection)?.into_string());
+    }
+    let sink = match trade_in.sink {
+        Some(sink) => Some(validate_addr(api, "sink", &sink)?.into_string()),
+        None => None,
+    };
+    Ok(TradeIn {
+        collections,
+        count: trade_in.count,
+        sink,
+    })
+}
+
+/// Burns the traded in NFTs, or sends them to the sink of the box.
+pub fn use_trade_in(
+    fbox: &FortuneBox,
+    nfts: &[TradedNft],
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let trade_in = match &fbox.trade_in {
+        Some(trade_in) => trade_in,
+        None => return Err(ContractError::InvalidTradeIn {}),
+    };
+    let mut msgs = Vec::with_capacity(nfts.len());
+    for nft in nfts.iter() {
+        let msg = match &trade_in.sink {
+            Some(sink) => to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: sink.clone(),
+                token_id: nft.token_id.clone(),
+            })?,
+            None => to_json_binary(&Cw721BurnMsg::Burn {
+                token_id: nft.token_id.clone(),
+            })?,
+        };
+        msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: nft.collection.clone(),
+            msg,
+            funds: vec![],
+        }));
+    }
+    Ok(msgs)
+}
+
+/// Sends `nfts` back to `recipient`.
+pub fn return_nfts(nfts: Vec<TradedNft>, recipient: &Addr) -> StdResult<Vec<CosmosMsg>> {
+    let mut msgs = Vec::with_capacity(nfts.len());
+    for nft in nfts {
+        msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: nft.collection,
+            msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: recipient.to_string(),
+                token_id: nft.token_id,
+            })?,
+            funds: vec![],
+        }));
+    }
+    Ok(msgs)
+}
+
+/// Hands the keys held by a closing box over to its creator.
+pub fn release_keys(
+    storage: &mut dyn Storage,
+    fbox: &mut FortuneBox,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut msgs = Vec::new();
+    match &fbox.key {
+        Some(BoxKey::Nft { collection, .. }) => {
+            for token_id in fbox.held_keys.drain(..) {
+                msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: collection.clone(),
+                    msg: to_json_binary(
+                        &(Cw721ExecuteMsg::TransferNft {
+                            token_id,
+                            recipient: fbox.creator.to_string(),
+                        }),
+                    )?,
+                    funds: vec![],
+                }));
+            }
+        }
+        Some(BoxKey::Cw20 { token, .. }) if !fbox.held_key_amount.is_zero() => {
+            let held = Asset {
+                info: AssetInfo::Cw20 {
+                    contract_addr: Addr::unchecked(token),
+                },
+                amount: fbox.held_key_amount,
+            };
+            release_escrow(storage, &held)?;
+            msgs.push(transfer_token_message(held, fbox.creator.clone())?);
+            fbox.held_key_amount = Uint128::zero();
+        }
+        _ => {}
+    }
+    Ok(msgs)
+}
+
 pub fn execute_update_config(
     storage: &mut dyn Storage,
     address: Addr,
@@ -34,65 +730,979 @@ pub fn execute_update_config(
 
     Ok(Response::new()
         .add_attribute("action", "update_config")
-        .add_attribute("native_token", native_token.clone())
-        .add_attribute("feature_fees", feature_fees.clone())
-    )
+        .add_attribute("native_token", native_token)
+        .add_attribute("feature_fees", feature_fees))
 }
 
-pub fn transfer_token_message(
-    denom: String,
-    token_type: String,
-    amount: Uint128,
-    receiver: Addr,
-) -> Result<CosmosMsg, ContractError> {
-    if token_type == "native" {
-
-        return Ok((BankMsg::Send {
-            to_address: receiver.clone().into(),
-            amount: vec![Coin {
-                denom: denom.clone(),
-                amount,
-            }],
-        })
-        .into());
-    } else {
-
-        return Ok(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: denom.clone().into(),
+pub fn transfer_token_message(asset: Asset, receiver: Addr) -> Result<CosmosMsg, ContractError> {
+    let amount = asset.amount;
+    match asset.info {
+        AssetInfo::Native { denom } => Ok(BankMsg::Send {
+            to_address: receiver.into(),
+            amount: vec![Coin { denom, amount }],
+        }
+        .into()),
+        AssetInfo::Cw20 { contract_addr } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
             funds: vec![],
             msg: to_json_binary(
                 &(Cw20ExecuteMsg::Transfer {
-                    recipient: receiver.clone().into(),
+                    recipient: receiver.into(),
                     amount,
                 }),
             )?,
-        }));
+        })),
     }
 }
 
+/// Messages handing `count` units of an escrowed reward over to `receiver`.
+pub fn reward_transfer_messages(
+    storage: &mut dyn Storage,
+    reward: &RewardData,
+    count: u64,
+    receiver: Addr,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    if count == 0 {
+        return Ok(vec![]);
+    }
+    for asset in reward_assets(reward) {
+        release_escrow(
+            storage,
+            &Asset {
+                info: asset.info,
+                amount: asset.amount * Uint128::from(count),
+            },
+        )?;
+    }
+
+    match reward.reward_type {
+        RewardType::Nft => Ok(vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: reward.collection_addr.clone().unwrap().to_string(),
+            msg: to_json_binary(
+                &(Cw721ExecuteMsg::TransferNft {
+                    token_id: reward.nft_id.clone().unwrap(),
+                    recipient: receiver.into(),
+                }),
+            )?,
+            funds: vec![],
+        })]),
+        RewardType::TokenFactory | RewardType::Cw20 => {
+            let asset = reward.asset.clone().unwrap();
+            Ok(vec![transfer_token_message(
+                Asset {
+                    info: asset.info,
+                    amount: asset.amount * Uint128::from(count),
+                },
+                receiver,
+            )?])
+        }
+        // Bundles always have a count of 1, their NFTs are unique
+        RewardType::Bundle => reward
+            .bundle
+            .iter()
+            .map(|item| bundle_item_message(item, receiver.clone()))
+            .collect(),
+    }
+}
+
+/// Fungible assets paid out per win of `reward`.
+pub fn reward_assets(reward: &RewardData) -> Vec<Asset> {
+    match reward.reward_type {
+        RewardType::Bundle => reward
+            .bundle
+            .iter()
+            .filter_map(|item| match item {
+                BundleItem::Token { asset } => Some(asset.clone()),
+                BundleItem::Nft { .. } => None,
+            })
+            .collect(),
+        _ => reward.asset.clone().into_iter().collect(),
+    }
+}
+
+pub fn bundle_item_message(item: &BundleItem, receiver: Addr) -> Result<CosmosMsg, ContractError> {
+    match item {
+        BundleItem::Nft {
+            collection,
+            token_id,
+        } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: collection.clone(),
+            msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                token_id: token_id.clone(),
+                recipient: receiver.into(),
+            })?,
+            funds: vec![],
+        })),
+        BundleItem::Token { asset } => transfer_token_message(asset.clone(), receiver),
+    }
+}
+
+/// Highest reward id in use by the box, counting bundles still being deposited.
+pub fn last_reward_id(storage: &dyn Storage, fbox: &FortuneBox) -> StdResult<u64> {
+    let last = fbox.rewards.iter().map(|x| x.id).max().unwrap_or(0);
+    let pending = PENDING_BUNDLES
+        .prefix(fbox.id.clone())
+        .keys(storage, None, None, Order::Descending)
+        .next()
+        .transpose()?
+        .unwrap_or(0);
+    Ok(last.max(pending))
+}
+
+pub fn validate_bundle(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    items: Vec<BundleItem>,
+) -> Result<Vec<BundleItem>, ContractError> {
+    if items.is_empty() {
+        return Err(ContractError::InvalidBundle {});
+    }
+    let mut validated = Vec::with_capacity(items.len());
+    for item in items {
+        validated.push(match item {
+            BundleItem::Nft {
+                collection,
+                token_id,
+            } => BundleItem::Nft {
+                collection: validate_addr(api, "collection", &collection)?.into_string(),
+                token_id,
+            },
+            BundleItem::Token { asset } => {
+                if asset.amount.is_zero() {
+                    return Err(ContractError::InvalidBundle {});
+                }
+                let info = asset.info.validate(api)?;
+                allowed_asset(storage, &info)?;
+                BundleItem::Token {
+                    asset: Asset {
+                        info,
+                        amount: asset.amount,
+                    },
+                }
+            }
+        });
+    }
+    Ok(validated)
+}
+
+/// Marks the first outstanding item equal to `item` as deposited.
+pub fn deposit_bundle_item(
+    bundle: &mut PendingBundle,
+    item: &BundleItem,
+) -> Result<(), ContractError> {
+    let slot = bundle
+        .reward
+        .bundle
+        .iter()
+        .zip(bundle.deposited.iter_mut())
+        .find(|(expected, deposited)| !**deposited && *expected == item);
+    match slot {
+        Some((_, deposited)) => {
+            *deposited = true;
+            Ok(())
+        }
+        None => Err(ContractError::InvalidBundle {}),
+    }
+}
+
+/// Returns the deposited items of an unfinished bundle to `receiver`.
+pub fn release_bundle(
+    storage: &mut dyn Storage,
+    bundle: &PendingBundle,
+    receiver: Addr,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut msgs = vec![];
+    for (item, _) in bundle
+        .reward
+        .bundle
+        .iter()
+        .zip(bundle.deposited.iter())
+        .filter(|(_, deposited)| **deposited)
+    {
+        if let BundleItem::Token { asset } = item {
+            release_escrow(storage, asset)?;
+        }
+        msgs.push(bundle_item_message(item, receiver.clone())?);
+    }
+    Ok(msgs)
+}
+
 pub fn get_token_amount(
     querier: QuerierWrapper,
-    denom: String,
-    contract_addr: Addr,
-    token_type: String,
+    asset: &AssetInfo,
+    address: Addr,
 ) -> Result<Uint128, ContractError> {
-    if token_type == "native" {
-        let native_response: NativeBalanceResponse =
-            querier.query(&QueryRequest::Bank(BankQuery::Balance {
-                address: contract_addr.clone().into(),
-                denom: denom.clone(),
-            }))?;
-        return Ok(native_response.amount.amount);
-    } else {
-        let balance_response: CW20BalanceResponse =
-            querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-                contract_addr: denom.clone().into(),
-                msg: to_json_binary(
-                    &(Cw20QueryMsg::Balance {
-                        address: contract_addr.clone().into(),
-                    }),
-                )?,
-            }))?;
-        return Ok(balance_response.balance);
+    match asset {
+        AssetInfo::Native { denom } => {
+            let native_response: NativeBalanceResponse =
+                querier.query(&QueryRequest::Bank(BankQuery::Balance {
+                    address: address.into(),
+                    denom: denom.clone(),
+                }))?;
+            Ok(native_response.amount.amount)
+        }
+        AssetInfo::Cw20 { contract_addr } => {
+            let balance_response: CW20BalanceResponse =
+                querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr: contract_addr.to_string(),
+                    msg: to_json_binary(
+                        &(Cw20QueryMsg::Balance {
+                            address: address.into(),
+                        }),
+                    )?,
+                }))?;
+            Ok(balance_response.balance)
+        }
+    }
+}
+
+pub fn execute_register_asset(
+    deps: DepsMut,
+    info: MessageInfo,
+    asset: AssetInfo,
+    decimals: Option<u64>,
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender)?;
+    let asset = asset.validate(deps.api)?;
+
+    let decimals = match &asset {
+        AssetInfo::Native { .. } => match decimals {
+            Some(decimals) => decimals,
+            None => {
+                return Err(ContractError::MissingDecimals {});
+            }
+        },
+        AssetInfo::Cw20 { contract_addr } => {
+            let token_info: TokenInfoResponse = deps
+                .querier
+                .query_wasm_smart(contract_addr, &Cw20QueryMsg::TokenInfo {})?;
+            let expected = token_info.decimals as u64;
+            if decimals.is_some_and(|decimals| decimals != expected) {
+                return Err(ContractError::DecimalsMismatch { expected });
+            }
+            expected
+        }
+    };
+
+    let key = asset.key();
+    ALLOWED_ASSETS.save(
+        deps.storage,
+        key.clone(),
+        &(AllowedAsset {
+            info: asset,
+            decimals,
+        }),
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_asset")
+        .add_attribute("asset", key)
+        .add_attribute("decimals", decimals.to_string()))
+}
+
+pub fn execute_remove_asset(
+    storage: &mut dyn Storage,
+    address: Addr,
+    asset: AssetInfo,
+) -> Result<Response, ContractError> {
+    check_owner(storage, address)?;
+    ALLOWED_ASSETS.remove(storage, asset.key());
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_asset")
+        .add_attribute("asset", asset.key()))
+}
+
+/// Registry entry of `asset`, rejecting anything the owner has not allowed.
+pub fn allowed_asset(
+    storage: &dyn Storage,
+    asset: &AssetInfo,
+) -> Result<AllowedAsset, ContractError> {
+    match ALLOWED_ASSETS.may_load(storage, asset.key())? {
+        Some(allowed) if allowed.info == *asset => Ok(allowed),
+        _ => Err(ContractError::AssetNotAllowed { asset: asset.key() }),
+    }
+}
+
+/// Books `amount` of protocol fees into the treasury ledger.
+pub fn accrue_fee(storage: &mut dyn Storage, denom: String, amount: Uint128) -> StdResult<()> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    let mut balance = TREASURY
+        .may_load(storage, denom.clone())?
+        .unwrap_or_default();
+    balance.collected += amount;
+    TREASURY.save(storage, denom, &balance)
+}
+
+/// Basis points of a whole, the unit of the referral share.
+pub const MAX_BPS: u64 = 10_000;
+
+/// Share of every paid open kept as protocol fee, the jackpot can only take from the rest.
+pub const OPEN_FEE_BPS: u64 = 500;
+
+/// Credits `referrer` with its share of `fee` and returns that share, which is no longer
+/// protocol fee.
+pub fn pay_referral(storage: &mut dyn Storage, referrer: Addr, fee: &Asset) -> StdResult<Uint128> {
+    let cfg = CONFIG.load(storage)?;
+    let share = fee.amount.multiply_ratio(cfg.referral_bps, MAX_BPS);
+
+    let mut stats = REFERRALS
+        .may_load(storage, referrer.clone())?
+        .unwrap_or(ReferralStats {
+            address: referrer.clone(),
+            referrals: 0,
+            earned: vec![],
+        });
+    stats.referrals += 1;
+    if !share.is_zero() {
+        match stats.earned.iter_mut().find(|x| x.info == fee.info) {
+            Some(earned) => earned.amount += share,
+            None => stats.earned.push(Asset {
+                info: fee.info.clone(),
+                amount: share,
+            }),
+        }
+
+        let key = (referrer.clone(), fee.info.key());
+        let mut balance = REFERRAL_BALANCES
+            .may_load(storage, key.clone())?
+            .unwrap_or(Asset {
+                info: fee.info.clone(),
+                amount: Uint128::zero(),
+            });
+        balance.amount += share;
+        REFERRAL_BALANCES.save(storage, key, &balance)?;
+        escrow(
+            storage,
+            &Asset {
+                info: fee.info.clone(),
+                amount: share,
+            },
+        )?;
+    }
+    REFERRALS.save(storage, referrer, &stats)?;
+    Ok(share)
+}
+
+pub fn execute_update_referral_share(
+    storage: &mut dyn Storage,
+    address: Addr,
+    referral_bps: u64,
+) -> Result<Response, ContractError> {
+    check_owner(storage, address)?;
+    if referral_bps > MAX_BPS {
+        return Err(ContractError::InvalidReferralShare { max: MAX_BPS });
+    }
+
+    CONFIG.update(storage, |mut exists| -> StdResult<_> {
+        exists.referral_bps = referral_bps;
+        Ok(exists)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_referral_share")
+        .add_attribute("referral_bps", referral_bps.to_string()))
+}
+
+/// Pays out every referral balance of the sender.
+pub fn execute_claim_referral_rewards(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let balances = REFERRAL_BALANCES
+        .prefix(info.sender.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    if balances.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+
+    let mut msgs = Vec::with_capacity(balances.len());
+    for (key, balance) in balances {
+        REFERRAL_BALANCES.remove(deps.storage, (info.sender.clone(), key));
+        release_escrow(deps.storage, &balance)?;
+        msgs.push(transfer_token_message(balance, info.sender.clone())?);
+    }
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("action", "claim_referral_rewards")
+        .add_attribute("referrer", info.sender))
+}
+
+/// Most tickets a raffle can sell, every one of them is held in memory by the draw.
+pub const MAX_RAFFLE_TICKETS: u64 = 1_000;
+
+/// Rejects ticket limits the draw could not handle.
+pub fn validate_raffle(
+    min_tickets: Option<u64>,
+    max_tickets: Option<u64>,
+) -> Result<(), ContractError> {
+    let max_tickets = max_tickets.unwrap_or(MAX_RAFFLE_TICKETS);
+    if max_tickets == 0
+        || max_tickets > MAX_RAFFLE_TICKETS
+        || min_tickets.is_some_and(|min| min > max_tickets)
+    {
+        return Err(ContractError::InvalidRaffle {});
+    }
+    Ok(())
+}
+
+/// Denominator of `Config::jackpot_odds`.
+pub const JACKPOT_ODDS_SCALE: u64 = 1_000_000;
+
+pub fn execute_update_jackpot_config(
+    storage: &mut dyn Storage,
+    address: Addr,
+    jackpot_bps: u64,
+    jackpot_odds: u64,
+) -> Result<Response, ContractError> {
+    check_owner(storage, address)?;
+    if jackpot_bps > MAX_BPS - OPEN_FEE_BPS || jackpot_odds > JACKPOT_ODDS_SCALE {
+        return Err(ContractError::InvalidJackpotConfig {});
     }
+
+    CONFIG.update(storage, |mut exists| -> StdResult<_> {
+        exists.jackpot_bps = jackpot_bps;
+        exists.jackpot_odds = jackpot_odds;
+        Ok(exists)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_jackpot_config")
+        .add_attribute("jackpot_bps", jackpot_bps.to_string())
+        .add_attribute("jackpot_odds", jackpot_odds.to_string()))
+}
+
+pub fn execute_set_jackpot_seed(
+    storage: &mut dyn Storage,
+    address: Addr,
+    asset: AssetInfo,
+    seed: Uint128,
+) -> Result<Response, ContractError> {
+    check_owner(storage, address)?;
+    allowed_asset(storage, &asset)?;
+
+    let mut pool = JACKPOTS
+        .may_load(storage, asset.key())?
+        .unwrap_or(JackpotPool {
+            balance: Asset {
+                info: asset.clone(),
+                amount: Uint128::zero(),
+            },
+            seed,
+        });
+    pool.seed = seed;
+    JACKPOTS.save(storage, asset.key(), &pool)?;
+    let seeded = replenish_jackpot(storage, &asset)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_jackpot_seed")
+        .add_attribute("asset", asset.key())
+        .add_attribute("seed", seed)
+        .add_attribute("seeded", seeded))
+}
+
+/// Adds `amount` to the jackpot pool of its asset.
+pub fn feed_jackpot(storage: &mut dyn Storage, amount: Asset) -> StdResult<()> {
+    if amount.amount.is_zero() {
+        return Ok(());
+    }
+    escrow(storage, &amount)?;
+    let key = amount.info.key();
+    let pool = match JACKPOTS.may_load(storage, key.clone())? {
+        Some(mut pool) => {
+            pool.balance.amount += amount.amount;
+            pool
+        }
+        None => JackpotPool {
+            balance: amount,
+            seed: Uint128::zero(),
+        },
+    };
+    JACKPOTS.save(storage, key, &pool)
+}
+
+/// Tops the pool up to its seed out of the available treasury, returns the amount moved.
+pub fn replenish_jackpot(storage: &mut dyn Storage, asset: &AssetInfo) -> StdResult<Uint128> {
+    let mut pool = match JACKPOTS.may_load(storage, asset.key())? {
+        Some(pool) if pool.balance.amount < pool.seed => pool,
+        _ => return Ok(Uint128::zero()),
+    };
+    let mut treasury = TREASURY.may_load(storage, asset.key())?.unwrap_or_default();
+    let amount = (pool.seed - pool.balance.amount).min(treasury.collected - treasury.withdrawn);
+    if amount.is_zero() {
+        return Ok(amount);
+    }
+
+    treasury.withdrawn += amount;
+    TREASURY.save(storage, asset.key(), &treasury)?;
+    pool.balance.amount += amount;
+    JACKPOTS.save(storage, asset.key(), &pool)?;
+    escrow(
+        storage,
+        &Asset {
+            info: asset.clone(),
+            amount,
+        },
+    )?;
+    Ok(amount)
+}
+
+/// Rolls the jackpot from bytes 8..16 of the draw hash, the reward draw uses the first 8.
+pub fn jackpot_hit(hash: &[u8], jackpot_odds: u64) -> bool {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[8..16]);
+    u64::from_be_bytes(bytes) % JACKPOT_ODDS_SCALE < jackpot_odds
+}
+
+/// Empties the jackpot of `asset` to `winner` and refills it to its seed. Returns `None` when
+/// the pool is empty.
+pub fn win_jackpot(
+    storage: &mut dyn Storage,
+    asset: &AssetInfo,
+    winner: Addr,
+    box_id: String,
+    now: u64,
+) -> StdResult<Option<JackpotWin>> {
+    let mut pool = match JACKPOTS.may_load(storage, asset.key())? {
+        Some(pool) if !pool.balance.amount.is_zero() => pool,
+        _ => return Ok(None),
+    };
+    let prize = pool.balance.clone();
+    pool.balance.amount = Uint128::zero();
+    JACKPOTS.save(storage, asset.key(), &pool)?;
+    release_escrow(storage, &prize)?;
+    replenish_jackpot(storage, asset)?;
+
+    let id = JACKPOT_WIN_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    let win = JackpotWin {
+        id,
+        box_id,
+        winner,
+        prize,
+        time: now,
+    };
+    JACKPOT_WIN_COUNT.save(storage, &id)?;
+    JACKPOT_WINS.save(storage, id, &win)?;
+    Ok(Some(win))
+}
+
+/// Funds the contract holds on behalf of others: unwon and pending rewards, raffle pots, held
+/// keys, running auction bids, unclaimed referral earnings and jackpot pools.
+pub fn escrowed_amount(deps: Deps, asset: &AssetInfo) -> StdResult<Uint128> {
+    Ok(ESCROW
+        .may_load(deps.storage, asset.key())?
+        .unwrap_or_default())
+}
+
+/// Books `asset` as held on behalf of others, out of reach of treasury withdrawals.
+pub fn escrow(storage: &mut dyn Storage, asset: &Asset) -> StdResult<()> {
+    if asset.amount.is_zero() {
+        return Ok(());
+    }
+    let escrowed = ESCROW
+        .may_load(storage, asset.info.key())?
+        .unwrap_or_default();
+    ESCROW.save(
+        storage,
+        asset.info.key(),
+        &escrowed.checked_add(asset.amount)?,
+    )
+}
+
+/// Takes `asset` out of escrow as it leaves the contract or turns into protocol fees.
+pub fn release_escrow(storage: &mut dyn Storage, asset: &Asset) -> StdResult<()> {
+    if asset.amount.is_zero() {
+        return Ok(());
+    }
+    let escrowed = ESCROW
+        .may_load(storage, asset.info.key())?
+        .unwrap_or_default();
+    ESCROW.save(
+        storage,
+        asset.info.key(),
+        &escrowed.checked_sub(asset.amount)?,
+    )
+}
+
+pub fn execute_withdraw_treasury(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    asset: AssetInfo,
+    amount: Uint128,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender)?;
+    let recipient = validate_addr(deps.api, "recipient", &recipient)?;
+    let denom = asset.key();
+
+    let mut balance = TREASURY
+        .may_load(deps.storage, denom.clone())?
+        .unwrap_or_default();
+    if amount.is_zero() || balance.collected - balance.withdrawn < amount {
+        return Err(ContractError::InsufficientTreasury {});
+    }
+
+    // Fees are only ever paid out of what is left over after escrow
+    let held = get_token_amount(deps.querier, &asset, env.contract.address)?;
+    let escrowed = escrowed_amount(deps.as_ref(), &asset)?;
+    if held < escrowed + amount {
+        return Err(ContractError::EscrowViolation {});
+    }
+
+    balance.withdrawn += amount;
+    TREASURY.save(deps.storage, denom.clone(), &balance)?;
+
+    Ok(Response::new()
+        .add_message(transfer_token_message(
+            Asset {
+                info: asset,
+                amount,
+            },
+            recipient.clone(),
+        )?)
+        .add_attribute("action", "withdraw_treasury")
+        .add_attribute("denom", denom)
+        .add_attribute("amount", amount)
+        .add_attribute("recipient", recipient))
+}
+
+pub fn execute_update_feature_config(
+    storage: &mut dyn Storage,
+    address: Addr,
+    feature_fees: Uint128,
+    feature_period: u64,
+    max_featured: u64,
+) -> Result<Response, ContractError> {
+    check_owner(storage, address)?;
+
+    CONFIG.update(storage, |mut exists| -> StdResult<_> {
+        exists.feature_fees = feature_fees;
+        exists.feature_period = feature_period;
+        exists.max_featured = max_featured;
+        Ok(exists)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_feature_config")
+        .add_attribute("feature_fees", feature_fees)
+        .add_attribute("feature_period", feature_period.to_string())
+        .add_attribute("max_featured", max_featured.to_string()))
+}
+
+pub fn execute_update_odds_bounds(
+    storage: &mut dyn Storage,
+    address: Addr,
+    min_box_odds: u64,
+    max_box_odds: u64,
+) -> Result<Response, ContractError> {
+    check_owner(storage, address)?;
+
+    let cfg = CONFIG.load(storage)?;
+    // The default denominator has to stay a valid choice for new boxes
+    if min_box_odds == 0 || cfg.max_odds < min_box_odds || cfg.max_odds > max_box_odds {
+        return Err(ContractError::InvalidMaxOdds {
+            min: min_box_odds,
+            max: max_box_odds,
+        });
+    }
+
+    CONFIG.update(storage, |mut exists| -> StdResult<_> {
+        exists.min_box_odds = min_box_odds;
+        exists.max_box_odds = max_box_odds;
+        Ok(exists)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_odds_bounds")
+        .add_attribute("min_box_odds", min_box_odds.to_string())
+        .add_attribute("max_box_odds", max_box_odds.to_string()))
+}
+
+/// Status of the box at `now`, applying the time based moves the stored status lags behind on.
+pub fn box_status(fortune_box: &FortuneBox, now: u64) -> BoxStatus {
+    let expired = fortune_box.end_time.is_some_and(|end_time| now >= end_time);
+    match fortune_box.status {
+        BoxStatus::Scheduled | BoxStatus::Live | BoxStatus::Paused if expired => BoxStatus::Expired,
+        BoxStatus::Scheduled if fortune_box.start_time.is_none_or(|start| now >= start) => {
+            BoxStatus::Live
+        }
+        BoxStatus::Paused
+            if fortune_box
+                .resume_at
+                .is_some_and(|resume_at| now >= resume_at) =>
+        {
+            BoxStatus::Live
+        }
+        ref status => status.clone(),
+    }
+}
+
+/// Longest a box, auction or reroll window can run, ten years in seconds.
+pub const MAX_DURATION: u64 = 10 * 365 * 24 * 60 * 60;
+
+/// Rejects durations long enough to overflow the timestamps derived from them.
+pub fn validate_duration(duration: u64) -> Result<u64, ContractError> {
+    if duration > MAX_DURATION {
+        return Err(ContractError::InvalidDuration { max: MAX_DURATION });
+    }
+    Ok(duration)
+}
+
+/// Takes a closed box off the featured list.
+pub fn unfeature(storage: &mut dyn Storage, fortune_box: &mut FortuneBox) {
+    FEATURED.remove(storage, fortune_box.id.clone());
+    fortune_box.featured_until = None;
+}
+
+/// Only the box creator and the contract owners may manage a box.
+pub fn check_box_admin(
+    storage: &mut dyn Storage,
+    fortune_box: &FortuneBox,
+    address: Addr,
+) -> Result<(), ContractError> {
+    if fortune_box.creator != address {
+        check_owner(storage, address)?;
+    }
+    Ok(())
+}
+
+/// Rejects any status change the box lifecycle does not allow.
+pub fn check_transition(from: &BoxStatus, to: &BoxStatus) -> Result<(), ContractError> {
+    let allowed = matches!(
+        (from, to),
+        (BoxStatus::Draft, BoxStatus::Scheduled)
+            | (BoxStatus::Draft, BoxStatus::Live)
+            | (BoxStatus::Draft, BoxStatus::Cancelled)
+            | (BoxStatus::Scheduled, BoxStatus::Live)
+            | (BoxStatus::Scheduled, BoxStatus::Cancelled)
+            | (BoxStatus::Live, BoxStatus::Paused)
+            | (BoxStatus::Live, BoxStatus::SoldOut)
+            | (BoxStatus::Live, BoxStatus::Cancelled)
+            | (BoxStatus::Paused, BoxStatus::Live)
+            | (BoxStatus::Paused, BoxStatus::Cancelled)
+            | (BoxStatus::Expired, BoxStatus::Cancelled)
+    );
+    if !allowed {
+        return Err(ContractError::InvalidTransition {
+            from: format!("{:?}", from),
+            to: format!("{:?}", to),
+        });
+    }
+    Ok(())
+}
+
+/// Rejects reward changes once the box has left `Draft`.
+pub fn check_draft(fortune_box: &FortuneBox) -> Result<(), ContractError> {
+    if fortune_box.status != BoxStatus::Draft {
+        return Err(ContractError::BoxLocked {});
+    }
+    if fortune_box.series.is_some() {
+        return Err(ContractError::SeriesBox {});
+    }
+    Ok(())
+}
+
+pub fn has_pending_bundles(storage: &dyn Storage, box_id: String) -> bool {
+    PENDING_BUNDLES
+        .prefix(box_id)
+        .keys(storage, None, None, Order::Ascending)
+        .next()
+        .is_some()
+}
+
+/// Copies of the series rewards for one of its boxes, with `odds` overriding the series odds.
+pub fn series_rewards(
+    series: &Series,
+    odds: &[SeriesOdds],
+) -> Result<Vec<RewardData>, ContractError> {
+    if odds
+        .iter()
+        .any(|x| !series.rewards.iter().any(|reward| reward.id == x.reward_id))
+    {
+        return Err(ContractError::RewardNotFound {});
+    }
+    Ok(series
+        .rewards
+        .iter()
+        .map(|reward| RewardData {
+            odds: odds
+                .iter()
+                .find(|x| x.reward_id == reward.id)
+                .map_or(reward.odds, |x| x.odds),
+            ..reward.clone()
+        })
+        .collect())
+}
+
+/// Shares the reward counts of series box `fortune_box` with its series and the other boxes.
+pub fn sync_series(
+    storage: &mut dyn Storage,
+    fortune_box: &FortuneBox,
+    now: u64,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let series_id = match &fortune_box.series {
+        Some(series_id) => series_id.clone(),
+        None => return Ok(vec![]),
+    };
+    let mut series = SERIES.load(storage, series_id)?;
+    for reward in series.rewards.iter_mut() {
+        if let Some(drawn) = fortune_box.rewards.iter().find(|x| x.id == reward.id) {
+            reward.count = drawn.count;
+        }
+    }
+    save_series(storage, &series, Some(&fortune_box.id), now)
+}
+
+/// Saves `series` and mirrors its counts into its boxes, except `skip` which the caller saves.
+/// Once the stock runs out every box still running sells out, returning the messages that
+/// hand their held keys back.
+pub fn save_series(
+    storage: &mut dyn Storage,
+    series: &Series,
+    skip: Option<&str>,
+    now: u64,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let sold_out = series.rewards.iter().all(|x| x.count == 0);
+    let mut msgs = vec![];
+    for box_id in series.boxes.iter().filter(|x| Some(x.as_str()) != skip) {
+        let mut member = BOX_MAP.load(storage, box_id.clone())?;
+        for reward in member.rewards.iter_mut() {
+            if let Some(stock) = series.rewards.iter().find(|x| x.id == reward.id) {
+                reward.count = stock.count;
+            }
+        }
+        let running = matches!(
+            box_status(&member, now),
+            BoxStatus::Scheduled | BoxStatus::Live | BoxStatus::Paused
+        );
+        if sold_out && running {
+            member.status = BoxStatus::SoldOut;
+            member.resume_at = None;
+            msgs.extend(release_keys(storage, &mut member)?);
+            unfeature(storage, &mut member);
+        }
+        BOX_MAP.save(storage, box_id.clone(), &member)?;
+    }
+    SERIES.save(storage, series.id.clone(), series)?;
+    Ok(msgs)
+}
+
+/// Rejects `added_odds` when the box rewards would no longer fit in its `max_odds`.
+pub fn check_odds(fortune_box: &FortuneBox, added_odds: u64) -> Result<(), ContractError> {
+    //sum all odds in fortune_box.rewards
+    let current_max_odds = fortune_box
+        .rewards
+        .iter()
+        .fold(0, |acc, next| acc + next.odds);
+
+    let total = current_max_odds.checked_add(added_odds);
+    if total.is_none_or(|total| total > fortune_box.max_odds) {
+        return Err(ContractError::MaxOddsReached {
+            msg: format!(
+                "{}+{}/{}",
+                current_max_odds, added_odds, fortune_box.max_odds
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Result of a single draw against a box.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrawOutcome {
+    Reward(u64),
+    Empty,
+}
+
+/// Draw weight of every reward in the box, in reward order, plus the weight of the empty
+/// outcome. Exhausted rewards weigh nothing, and nothing is drawable once they all are.
+pub fn outcome_weights(fortune_box: &FortuneBox) -> (Vec<(u64, u128)>, u128) {
+    let weights: Vec<(u64, u128)> = fortune_box
+        .rewards
+        .iter()
+        .map(|reward| {
+            let weight = match (reward.count, &fortune_box.odds_model) {
+                (0, _) => 0,
+                (_, OddsModel::Fixed) => reward.odds as u128,
+                (count, OddsModel::StockWeighted) => (reward.odds as u128) * (count as u128),
+            };
+            (reward.id, weight)
+        })
+        .collect();
+    let total: u128 = weights.iter().map(|(_, weight)| weight).sum();
+    if !fortune_box.allow_empty || total == 0 {
+        return (weights, 0);
+    }
+
+    // Live rewards share `live_odds / max_odds` of the range, the remainder draws nothing
+    let live_odds: u128 = fortune_box
+        .rewards
+        .iter()
+        .filter(|reward| reward.count > 0)
+        .map(|reward| reward.odds as u128)
+        .sum();
+    let empty = (fortune_box.max_odds as u128).saturating_sub(live_odds) * total;
+    let weights = weights
+        .into_iter()
+        .map(|(reward_id, weight)| (reward_id, weight * live_odds))
+        .collect();
+    (weights, empty)
+}
+
+/// Picks the outcome hit by `seed`, or `None` when nothing is left to draw. Like
+/// `sample_index`, draws from the top of the u128 range are rehashed so every outcome keeps
+/// exactly its share of the total weight.
+pub fn draw_outcome(fortune_box: &FortuneBox, seed: &[u8]) -> Option<DrawOutcome> {
+    let (weights, empty) = outcome_weights(fortune_box);
+    let total: u128 = weights.iter().map(|(_, weight)| weight).sum::<u128>() + empty;
+    if total == empty {
+        return None;
+    }
+
+    let rem = (u128::MAX % total + 1) % total;
+    let mut attempt = 0u64;
+    let mut roll = loop {
+        let mut hasher = Sha256::new();
+        hasher.update(seed);
+        hasher.update(attempt.to_be_bytes());
+        let hash = hasher.finalize();
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&hash[0..16]);
+        let value = u128::from_be_bytes(bytes);
+        if value <= u128::MAX - rem {
+            break value % total;
+        }
+        attempt += 1;
+    };
+    for (reward_id, weight) in weights {
+        if roll < weight {
+            return Some(DrawOutcome::Reward(reward_id));
+        }
+        roll -= weight;
+    }
+    Some(DrawOutcome::Empty)
+}
+
+/// Exact probability of each reward, and of drawing nothing, for the next open.
+pub fn outcome_probabilities(fortune_box: &FortuneBox) -> (Vec<(u64, Decimal)>, Decimal) {
+    let (weights, empty) = outcome_weights(fortune_box);
+    let total: u128 = weights.iter().map(|(_, weight)| weight).sum::<u128>() + empty;
+    if total == 0 {
+        let weights = weights
+            .into_iter()
+            .map(|(reward_id, _)| (reward_id, Decimal::zero()))
+            .collect();
+        return (weights, Decimal::zero());
+    }
+
+    let weights = weights
+        .into_iter()
+        .map(|(reward_id, weight)| (reward_id, Decimal::from_ratio(weight, total)))
+        .collect();
+    (weights, Decimal::from_ratio(empty, total))
 }